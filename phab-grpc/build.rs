@@ -1,4 +1,4 @@
-use tonic_build;
+#![allow(clippy::needless_return)]
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
   tonic_build::compile_protos("proto/app.proto").unwrap();
@@ -1,3 +1,5 @@
+#![allow(clippy::needless_return)]
+
 use tonic::transport::Server;
 
 #[tokio::main]
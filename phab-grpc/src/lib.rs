@@ -1 +1,3 @@
+#![allow(clippy::needless_return, clippy::diverging_sub_expression)]
+
 pub mod task_service;
@@ -0,0 +1,594 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use futures::future::BoxFuture;
+use futures::future::FutureExt;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+use serde_json::Value;
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::client::config::CacheConfig;
+use crate::client::phabricator::ErrorType;
+use crate::client::phabricator::SEARCH_CONSTRAINT_CHUNK_SIZE;
+use crate::client::transport::is_read_method;
+use crate::client::transport::ConduitTransport;
+use crate::types::ResultAnyError;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct CacheEntry {
+  /// Epoch timestamp in seconds.
+  cached_at: u64,
+  /// Latest `dateModified` of the result items, used to check
+  /// whether the result changed once the entry expires.
+  date_modified: Option<u64>,
+  result: Value,
+}
+
+impl CacheEntry {
+  fn new(result: Value) -> CacheEntry {
+    let date_modified = result["data"].as_array().and_then(|items| {
+      return items
+        .iter()
+        .filter_map(|item| item["fields"]["dateModified"].as_u64())
+        .max();
+    });
+
+    return CacheEntry {
+      cached_at: now_secs(),
+      date_modified,
+      result,
+    };
+  }
+}
+
+fn now_secs() -> u64 {
+  return SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|duration| duration.as_secs())
+    .unwrap_or_default();
+}
+
+/// A line of the cache file.
+#[derive(Serialize, Deserialize)]
+struct CacheLine {
+  key: String,
+  entry: CacheEntry,
+}
+
+/// Conduit responses stored in a json lines file, keyed by method and params.
+/// A stored response is appended as a new line instead of rewriting the whole file,
+/// the latest line of a key wins. The file is compacted when it's loaded.
+struct ResponseCache {
+  filepath: PathBuf,
+  entries: HashMap<String, CacheEntry>,
+}
+
+impl ResponseCache {
+  /// Entries older than `max_age_secs` are pruned, every entry is kept if it's not set.
+  fn new(filepath: impl AsRef<Path>, max_age_secs: Option<u64>) -> ResultAnyError<ResponseCache> {
+    let filepath = PathBuf::from(filepath.as_ref());
+    let content = fs::read_to_string(&filepath).unwrap_or_default();
+    let now = now_secs();
+
+    let mut entries = HashMap::new();
+    let mut latest_lines: HashMap<String, &str> = HashMap::new();
+    let mut line_count = 0;
+
+    for line in content.lines() {
+      line_count += 1;
+
+      // Broken lines are not worth failing for, they're dropped on compaction
+      if let Ok(cache_line) = serde_json::from_str::<CacheLine>(line) {
+        latest_lines.insert(cache_line.key.clone(), line);
+        entries.insert(cache_line.key, cache_line.entry);
+      }
+    }
+
+    if let Some(max_age_secs) = max_age_secs {
+      entries.retain(|_, entry: &mut CacheEntry| {
+        return now.saturating_sub(entry.cached_at) <= max_age_secs;
+      });
+    }
+
+    let cache = ResponseCache { filepath, entries };
+
+    if cache.entries.len() != line_count {
+      let content: String = cache
+        .entries
+        .keys()
+        .map(|key| format!("{}\n", latest_lines[key]))
+        .collect();
+
+      cache.write(&content)?;
+    }
+
+    return Ok(cache);
+  }
+
+  /// `line` is the serialized `cache_line`.
+  fn append(&mut self, line: &str, cache_line: CacheLine) -> ResultAnyError<()> {
+    self
+      .open(OpenOptions::new().create(true).append(true))?
+      .write_all(line.as_bytes())?;
+
+    self.entries.insert(cache_line.key, cache_line.entry);
+
+    return Ok(());
+  }
+
+  fn clear(&mut self) -> ResultAnyError<()> {
+    self.entries.clear();
+
+    return self.write("");
+  }
+
+  fn write(&self, content: &str) -> ResultAnyError<()> {
+    self
+      .open(OpenOptions::new().create(true).write(true).truncate(true))?
+      .write_all(content.as_bytes())?;
+
+    return Ok(());
+  }
+
+  /// The cache holds private task content so the file is only readable by the current user.
+  fn open(&self, options: &mut OpenOptions) -> ResultAnyError<File> {
+    self.create_dir()?;
+
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::OpenOptionsExt;
+
+      options.mode(0o600);
+    }
+
+    let file = options.open(&self.filepath)?;
+
+    // The mode only applies to new files, an existing file is restricted as well
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::PermissionsExt;
+
+      file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+
+    return Ok(file);
+  }
+
+  fn create_dir(&self) -> ResultAnyError<()> {
+    if let Some(dir) = self.filepath.parent() {
+      fs::create_dir_all(dir)?;
+    }
+
+    return Ok(());
+  }
+}
+
+/// Identifies whose responses an entry holds so a cache file shared by several
+/// configs never serves responses of another host or account. Only a hash of
+/// the api token is kept in the cache file.
+fn cache_scope(host: &str, api_token: &str) -> String {
+  let token_hash: String = Sha256::digest(api_token.as_bytes())
+    .iter()
+    .map(|byte| format!("{:02x}", byte))
+    .collect();
+
+  return format!("{} {}", host.trim_end_matches('/'), token_hash);
+}
+
+fn cache_key(scope: &str, method: &str, params: &Value) -> String {
+  return format!("{} {} {}", scope, method, params);
+}
+
+/// Serve read calls from an on-disk cache, responses are refetched after `ttl_secs`
+/// unless the result can be validated as unchanged. Any successful edit
+/// clears the cache because we can't tell which responses it affects.
+///
+/// In offline mode only cached responses are served, regardless of their age.
+pub struct CachingTransport<T: ConduitTransport> {
+  inner: T,
+  cache: Mutex<ResponseCache>,
+  scope: String,
+  ttl_secs: u64,
+  offline: bool,
+}
+
+impl<T: ConduitTransport> CachingTransport<T> {
+  /// `host` and `api_token` are the ones `inner` sends requests with.
+  pub fn new(
+    inner: T,
+    config: &CacheConfig,
+    host: &str,
+    api_token: &str,
+  ) -> ResultAnyError<CachingTransport<T>> {
+    let path = config.path.as_ref().ok_or_else(|| {
+      return ErrorType::ValidationError {
+        message: String::from("Cache path is not set"),
+      };
+    })?;
+
+    return Ok(CachingTransport {
+      inner,
+      // Offline mode serves cached responses regardless of their age
+      cache: Mutex::new(ResponseCache::new(
+        path,
+        Some(config.ttl_secs).filter(|_| !config.offline),
+      )?),
+      scope: cache_scope(host, api_token),
+      ttl_secs: config.ttl_secs,
+      offline: config.offline,
+    });
+  }
+
+  async fn call_with_cache(&self, method: &str, params: &Value) -> ResultAnyError<Value> {
+    if !is_read_method(method) {
+      if self.offline {
+        return Err(
+          ErrorType::ValidationError {
+            message: format!("Cannot call {} in offline mode", method),
+          }
+          .into(),
+        );
+      }
+
+      let result = self.inner.call(method, params).await?;
+
+      self.cache.lock().unwrap().clear()?;
+
+      return Ok(result);
+    }
+
+    let key = cache_key(&self.scope, method, params);
+    let entry = self.cache.lock().unwrap().entries.get(&key).cloned();
+
+    if self.offline {
+      return entry.map(|entry| entry.result).ok_or_else(|| {
+        return ErrorType::ValidationError {
+          message: format!(
+            "{} with params {} is not cached, run it once without offline mode",
+            method, params
+          ),
+        }
+        .into();
+      });
+    }
+
+    if let Some(entry) = entry {
+      if now_secs().saturating_sub(entry.cached_at) < self.ttl_secs {
+        log::debug!("Serving {} from cache", key);
+
+        return Ok(entry.result);
+      }
+
+      if self.is_unchanged(method, params, &entry).await? {
+        log::debug!("Cache entry {} is still fresh", key);
+
+        return self.store(key, CacheEntry::new(entry.result));
+      }
+    }
+
+    let result = self.inner.call(method, params).await?;
+
+    return self.store(key, CacheEntry::new(result));
+  }
+
+  /// Only task searches can be checked cheaply, against the entry's latest `dateModified`.
+  /// Cached tasks are looked up by phid to catch tasks that changed, including the ones
+  /// that no longer match the query e.g. closed or unlinked from their parent. The query
+  /// is then rerun to catch tasks that newly match it.
+  async fn is_unchanged(
+    &self,
+    method: &str,
+    params: &Value,
+    entry: &CacheEntry,
+  ) -> ResultAnyError<bool> {
+    let date_modified = match entry.date_modified {
+      Some(date_modified) if method == "maniphest.search" => date_modified,
+      _ => return Ok(false),
+    };
+
+    let cached_phids: Vec<&str> = entry.result["data"]
+      .as_array()
+      .map(|items| {
+        items
+          .iter()
+          .filter_map(|item| item["phid"].as_str())
+          .collect()
+      })
+      .unwrap_or_default();
+
+    for phids in cached_phids.chunks(SEARCH_CONSTRAINT_CHUNK_SIZE) {
+      let validation_params = json!({
+        "constraints": {
+          "phids": phids,
+          "modifiedStart": date_modified + 1,
+        },
+        "limit": 1,
+      });
+
+      if !self.is_empty_result(method, &validation_params).await? {
+        return Ok(false);
+      }
+    }
+
+    let mut validation_params = params.clone();
+
+    validation_params["constraints"]["modifiedStart"] = json!(date_modified + 1);
+    validation_params["limit"] = json!(1);
+
+    return self.is_empty_result(method, &validation_params).await;
+  }
+
+  async fn is_empty_result(&self, method: &str, params: &Value) -> ResultAnyError<bool> {
+    let result = self.inner.call(method, params).await?;
+
+    return Ok(result["data"].as_array().map(Vec::is_empty) == Some(true));
+  }
+
+  fn store(&self, key: String, entry: CacheEntry) -> ResultAnyError<Value> {
+    let result = entry.result.clone();
+    let cache_line = CacheLine { key, entry };
+    // Serialized before taking the lock, only the append happens under it
+    let line = format!("{}\n", serde_json::to_string(&cache_line)?);
+
+    self.cache.lock().unwrap().append(&line, cache_line)?;
+
+    return Ok(result);
+  }
+}
+
+impl<T: ConduitTransport> ConduitTransport for CachingTransport<T> {
+  fn call<'a>(
+    &'a self,
+    method: &'a str,
+    params: &'a Value,
+  ) -> BoxFuture<'a, ResultAnyError<Value>> {
+    return self.call_with_cache(method, params).boxed();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::atomic::AtomicBool;
+  use std::sync::atomic::Ordering;
+  use std::sync::Arc;
+
+  use super::*;
+  use crate::client::transport::FakeTransport;
+
+  const HOST: &str = "https://phab.example.com";
+
+  fn cache_config(name: &str, ttl_secs: u64, offline: bool) -> CacheConfig {
+    let path =
+      std::env::temp_dir().join(format!("phab-cache-{}-{}.json", name, std::process::id()));
+
+    return CacheConfig {
+      path: Some(path.to_string_lossy().into_owned()),
+      ttl_secs,
+      offline,
+    };
+  }
+
+  fn task_search_transport(date_modified: u64) -> Arc<FakeTransport> {
+    return Arc::new(
+      FakeTransport::new().with_handler("maniphest.search", move |params| {
+        let data = match params["constraints"]["modifiedStart"].as_u64() {
+          Some(modified_start) if modified_start > date_modified => vec![],
+          _ => vec![json!({
+            "id": 1,
+            "phid": "PHID-TASK-1",
+            "fields": { "dateModified": date_modified },
+          })],
+        };
+
+        return Ok(json!({ "data": data }));
+      }),
+    );
+  }
+
+  #[tokio::test]
+  async fn test_serve_from_cache_until_ttl_and_offline() {
+    let config = cache_config("ttl", 3600, false);
+    let fake = task_search_transport(100);
+    let params = json!({ "constraints": { "ids": ["1"] } });
+
+    let caching = CachingTransport::new(fake.clone(), &config, HOST, "token").unwrap();
+
+    caching.call("maniphest.search", &params).await.unwrap();
+    caching.call("maniphest.search", &params).await.unwrap();
+
+    assert_eq!(fake.calls().len(), 1);
+
+    let offline = CachingTransport::new(
+      FakeTransport::new(),
+      &cache_config("ttl", 0, true),
+      HOST,
+      "token",
+    )
+    .unwrap();
+    let result = offline.call("maniphest.search", &params).await.unwrap();
+
+    assert_eq!(result["data"][0]["id"], json!(1));
+    assert!(offline.call("maniphest.search", &json!({})).await.is_err());
+    assert!(offline.call("maniphest.edit", &json!({})).await.is_err());
+
+    fs::remove_file(config.path.unwrap()).unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_expired_entry_is_validated_by_date_modified() {
+    let config = cache_config("validate", 0, false);
+    let fake = task_search_transport(100);
+    let params = json!({ "constraints": { "ids": ["1"] } });
+
+    let caching = CachingTransport::new(fake.clone(), &config, HOST, "token").unwrap();
+
+    caching.call("maniphest.search", &params).await.unwrap();
+    caching.call("maniphest.search", &params).await.unwrap();
+
+    let calls = fake.calls();
+
+    assert_eq!(calls.len(), 3);
+    assert_eq!(
+      calls[1].1["constraints"],
+      json!({ "phids": ["PHID-TASK-1"], "modifiedStart": 101 })
+    );
+    assert_eq!(calls[1].1["limit"], json!(1));
+    assert_eq!(
+      calls[2].1["constraints"],
+      json!({ "ids": ["1"], "modifiedStart": 101 })
+    );
+
+    fs::remove_file(config.path.unwrap()).unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_task_leaving_the_result_invalidates_entry() {
+    let config = cache_config("leave", 0, false);
+    let params = json!({ "constraints": { "statuses": ["open"] } });
+    let is_closed = Arc::new(AtomicBool::new(false));
+    let is_closed_in_fake = is_closed.clone();
+
+    // Once closed, task 1 is modified but no longer matches the open tasks query
+    let fake = Arc::new(
+      FakeTransport::new().with_handler("maniphest.search", move |params| {
+        let is_closed = is_closed_in_fake.load(Ordering::SeqCst);
+        let task = json!({
+          "id": 1,
+          "phid": "PHID-TASK-1",
+          "fields": { "dateModified": if is_closed { 200 } else { 100 } },
+        });
+
+        let data = match (is_closed, params["constraints"]["phids"].is_array()) {
+          (false, _) | (true, true) => vec![task],
+          (true, false) => vec![],
+        };
+
+        return Ok(json!({ "data": data }));
+      }),
+    );
+
+    let caching = CachingTransport::new(fake.clone(), &config, HOST, "token").unwrap();
+
+    caching.call("maniphest.search", &params).await.unwrap();
+    is_closed.store(true, Ordering::SeqCst);
+
+    let result = caching.call("maniphest.search", &params).await.unwrap();
+
+    assert_eq!(result["data"], json!([]));
+
+    let calls = fake.calls();
+
+    // Query, phid validation then refetch without validating by the query
+    assert_eq!(calls.len(), 3);
+    assert_eq!(calls[2].1, params);
+
+    fs::remove_file(config.path.unwrap()).unwrap();
+  }
+
+  #[test]
+  fn test_cache_scope_is_stable() {
+    assert_eq!(
+      cache_scope("https://phab.example.com/", "token"),
+      "https://phab.example.com 3c469e9d6c5875d37a43f353d4f88e61fcf812c66eee3457465a40b0da4153e0"
+    );
+  }
+
+  #[tokio::test]
+  async fn test_entries_are_scoped_to_host_and_api_token() {
+    let config = cache_config("scope", 3600, false);
+    let params = json!({ "constraints": { "ids": ["1"] } });
+
+    for (host, api_token) in [
+      (HOST, "token"),
+      (HOST, "other-token"),
+      ("https://other.example.com", "token"),
+    ] {
+      let fake = task_search_transport(100);
+      let caching = CachingTransport::new(fake.clone(), &config, host, api_token).unwrap();
+
+      caching.call("maniphest.search", &params).await.unwrap();
+
+      assert_eq!(fake.calls().len(), 1);
+    }
+
+    let content = fs::read_to_string(config.path.as_ref().unwrap()).unwrap();
+
+    assert!(!content.contains("other-token"));
+
+    fs::remove_file(config.path.unwrap()).unwrap();
+  }
+
+  #[test]
+  fn test_load_prunes_expired_entries_and_compacts_file() {
+    let path = cache_config("prune", 0, false).path.unwrap();
+    let line = |key: &str, cached_at: u64, result: Value| -> String {
+      let mut entry = CacheEntry::new(result);
+
+      entry.cached_at = cached_at;
+
+      let cache_line = CacheLine {
+        key: key.to_owned(),
+        entry,
+      };
+
+      return serde_json::to_string(&cache_line).unwrap();
+    };
+
+    let lines = [
+      line("expired", 0, json!(1)),
+      line("fresh", now_secs(), json!(1)),
+      String::from("{ broken"),
+      line("fresh", now_secs(), json!(2)),
+    ];
+
+    fs::write(&path, lines.join("\n")).unwrap();
+
+    let cache = ResponseCache::new(&path, None).unwrap();
+
+    assert_eq!(cache.entries.len(), 2);
+
+    let mut cache = ResponseCache::new(&path, Some(3600)).unwrap();
+
+    assert_eq!(cache.entries.len(), 1);
+    assert_eq!(cache.entries["fresh"].result, json!(2));
+    assert_eq!(
+      fs::read_to_string(&path).unwrap(),
+      format!("{}\n", lines[3])
+    );
+
+    let cache_line = CacheLine {
+      key: String::from("new"),
+      entry: CacheEntry::new(json!(3)),
+    };
+    let new_line = format!("{}\n", serde_json::to_string(&cache_line).unwrap());
+
+    cache.append(&new_line, cache_line).unwrap();
+
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::PermissionsExt;
+
+      let mode = fs::metadata(&path).unwrap().permissions().mode();
+
+      assert_eq!(mode & 0o777, 0o600);
+    }
+
+    assert_eq!(
+      fs::read_to_string(&path).unwrap(),
+      format!("{}\n{}", lines[3], new_line)
+    );
+
+    fs::remove_file(path).unwrap();
+  }
+}
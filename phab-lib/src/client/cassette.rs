@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::Error;
+use futures::future::BoxFuture;
+use futures::future::FutureExt;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::client::phabricator::ErrorType;
+use crate::client::transport::ConduitTransport;
+use crate::types::ResultAnyError;
+
+/// A single recorded conduit call.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Interaction {
+  pub method: String,
+  pub params: Value,
+  pub response: RecordedResponse,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordedResponse {
+  Result(Value),
+  /// `code` is only set for conduit errors, other failures (e.g. connection errors)
+  /// only keep their message.
+  Error {
+    code: Option<String>,
+    info: String,
+  },
+}
+
+/// Recorded conduit traffic, stored as a json file.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Cassette {
+  pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+  pub fn load(path: &Path) -> ResultAnyError<Cassette> {
+    let content = fs::read_to_string(path).map_err(|err| {
+      return ErrorType::ValidationError {
+        message: format!("Cannot read cassette {}, {}", path.display(), err),
+      };
+    })?;
+
+    return serde_json::from_str(&content).map_err(|err| {
+      return ErrorType::ParseError {
+        message: format!("Cannot parse cassette {}, {}", path.display(), err),
+      }
+      .into();
+    });
+  }
+
+  pub fn save(&self, path: &Path) -> ResultAnyError<()> {
+    let content = serde_json::to_string_pretty(self).map_err(Error::new)?;
+
+    return fs::write(path, content).map_err(Error::new);
+  }
+}
+
+/// Key to find the recorded response of a call.
+fn interaction_key(method: &str, params: &Value) -> String {
+  return format!("{} {}", method, params);
+}
+
+/// Forward calls to the inner transport and record every call into the cassette file,
+/// the file is rewritten after each call so it's complete even if the command fails.
+pub struct RecordingTransport<T: ConduitTransport> {
+  inner: T,
+  path: PathBuf,
+  cassette: Mutex<Cassette>,
+}
+
+impl<T: ConduitTransport> RecordingTransport<T> {
+  pub fn new(inner: T, path: impl Into<PathBuf>) -> RecordingTransport<T> {
+    return RecordingTransport {
+      inner,
+      path: path.into(),
+      cassette: Mutex::new(Cassette::default()),
+    };
+  }
+
+  async fn call_and_record(&self, method: &str, params: &Value) -> ResultAnyError<Value> {
+    let result = self.inner.call(method, params).await;
+
+    let response = match &result {
+      Ok(value) => RecordedResponse::Result(value.clone()),
+      Err(err) => match err.downcast_ref::<ErrorType>() {
+        Some(ErrorType::ConduitError { code, info, .. }) => RecordedResponse::Error {
+          code: Some(code.clone()),
+          info: info.clone(),
+        },
+        _ => RecordedResponse::Error {
+          code: None,
+          info: err.to_string(),
+        },
+      },
+    };
+
+    let mut cassette = self.cassette.lock().unwrap();
+
+    cassette.interactions.push(Interaction {
+      method: method.to_owned(),
+      params: params.clone(),
+      response,
+    });
+
+    cassette.save(&self.path)?;
+
+    return result;
+  }
+}
+
+impl<T: ConduitTransport> ConduitTransport for RecordingTransport<T> {
+  fn call<'a>(
+    &'a self,
+    method: &'a str,
+    params: &'a Value,
+  ) -> BoxFuture<'a, ResultAnyError<Value>> {
+    return self.call_and_record(method, params).boxed();
+  }
+}
+
+/// Answer calls from a recorded cassette without any server.
+///
+/// Calls are matched by method and params, identical calls get the recorded
+/// responses in the recorded order, e.g. a task fetched before and after an edit.
+/// The last response is repeated once they run out.
+pub struct ReplayTransport {
+  responses: Mutex<HashMap<String, VecDeque<RecordedResponse>>>,
+}
+
+impl ReplayTransport {
+  pub fn new(cassette: Cassette) -> ReplayTransport {
+    let mut responses: HashMap<String, VecDeque<RecordedResponse>> = HashMap::new();
+
+    for interaction in cassette.interactions {
+      responses
+        .entry(interaction_key(&interaction.method, &interaction.params))
+        .or_default()
+        .push_back(interaction.response);
+    }
+
+    return ReplayTransport {
+      responses: Mutex::new(responses),
+    };
+  }
+
+  pub fn from_file(path: &Path) -> ResultAnyError<ReplayTransport> {
+    return Cassette::load(path).map(ReplayTransport::new);
+  }
+
+  fn replay(&self, method: &str, params: &Value) -> ResultAnyError<Value> {
+    let mut responses = self.responses.lock().unwrap();
+
+    let recorded_responses = responses
+      .get_mut(&interaction_key(method, params))
+      .ok_or_else(|| {
+        return ErrorType::ValidationError {
+          message: format!("No recorded response for {} with params {}", method, params),
+        };
+      })?;
+
+    let response = if recorded_responses.len() > 1 {
+      recorded_responses.pop_front().unwrap()
+    } else {
+      recorded_responses[0].clone()
+    };
+
+    return match response {
+      RecordedResponse::Result(value) => Ok(value),
+      RecordedResponse::Error {
+        code: Some(code),
+        info,
+      } => Err(
+        ErrorType::ConduitError {
+          code,
+          info,
+          method: method.to_owned(),
+        }
+        .into(),
+      ),
+      RecordedResponse::Error { code: None, info } => Err(Error::msg(info)),
+    };
+  }
+}
+
+impl ConduitTransport for ReplayTransport {
+  fn call<'a>(
+    &'a self,
+    method: &'a str,
+    params: &'a Value,
+  ) -> BoxFuture<'a, ResultAnyError<Value>> {
+    return futures::future::ready(self.replay(method, params)).boxed();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use serde_json::json;
+
+  use super::*;
+  use crate::client::transport::FakeTransport;
+
+  #[tokio::test]
+  async fn test_replay_recorded_calls() {
+    let path = std::env::temp_dir().join(format!("phab-cassette-{}.json", std::process::id()));
+
+    let fake = FakeTransport::new()
+      .with_result("user.whoami", json!({ "phid": "PHID-USER-1" }))
+      .with_result("maniphest.search", json!({ "data": [] }));
+
+    let recording = RecordingTransport::new(fake, &path);
+    let params = json!({ "constraints": { "ids": ["1"] } });
+
+    recording.call("user.whoami", &json!({})).await.unwrap();
+    recording.call("maniphest.search", &params).await.unwrap();
+    recording
+      .call("project.search", &json!({}))
+      .await
+      .err()
+      .unwrap();
+
+    let replay = ReplayTransport::from_file(&path).unwrap();
+
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+      replay.call("maniphest.search", &params).await.unwrap(),
+      json!({ "data": [] })
+    );
+
+    let err = replay
+      .call("project.search", &json!({}))
+      .await
+      .err()
+      .unwrap();
+
+    assert!(matches!(
+      err.downcast_ref::<ErrorType>(),
+      Some(ErrorType::ConduitError { code, .. }) if code == "ERR-CONDUIT-CALL"
+    ));
+
+    assert!(replay
+      .call("maniphest.search", &json!({}))
+      .await
+      .err()
+      .unwrap()
+      .to_string()
+      .contains("No recorded response for maniphest.search"));
+  }
+}
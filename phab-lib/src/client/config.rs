@@ -1,3 +1,6 @@
+use std::time::Duration;
+
+use rand::Rng;
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize)]
@@ -6,9 +9,156 @@ pub struct CertIdentityConfig {
   pub pkcs12_password: String,
 }
 
+/// Retry policy for transient http failures, only read methods (e.g. `*.search`)
+/// are retried on failure responses because an edit could have been applied
+/// even though the proxy in front of phabricator responds with an error.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+  /// Total number of attempts including the first one, 1 means no retry.
+  pub max_attempts: u32,
+  pub initial_backoff_ms: u64,
+  pub max_backoff_ms: u64,
+  /// Http statuses that will be retried.
+  pub retry_on_statuses: Vec<u16>,
+}
+
+impl Default for RetryConfig {
+  fn default() -> RetryConfig {
+    return RetryConfig {
+      max_attempts: 3,
+      initial_backoff_ms: 200,
+      max_backoff_ms: 5000,
+      retry_on_statuses: vec![429, 502, 503, 504],
+    };
+  }
+}
+
+impl RetryConfig {
+  /// Exponential backoff before the next attempt, randomized between
+  /// half and the full backoff so concurrent requests don't retry at once.
+  pub fn backoff(&self, attempt: u32) -> Duration {
+    let backoff_ms = self
+      .initial_backoff_ms
+      .saturating_mul(2u64.saturating_pow(attempt.saturating_sub(1)))
+      .min(self.max_backoff_ms);
+
+    let jittered_ms = rand::thread_rng().gen_range(backoff_ms / 2..=backoff_ms);
+
+    return Duration::from_millis(jittered_ms);
+  }
+
+  /// Wait requested by the server's `Retry-After`, capped to `max_backoff_ms`
+  /// so a misbehaving server can't stall the client.
+  pub fn retry_after(&self, retry_after: Duration) -> Duration {
+    return retry_after.min(Duration::from_millis(self.max_backoff_ms));
+  }
+}
+
+/// Client side token bucket rate limit, shared by every request of the client.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RateLimitConfig {
+  pub requests_per_second: f64,
+  /// Number of requests that can be sent at once before being limited.
+  pub burst: u32,
+}
+
+/// On-disk cache of conduit read responses.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CacheConfig {
+  /// Cache file path, it has to be set before creating the client.
+  pub path: Option<String>,
+  /// Cached responses older than this are validated or refetched.
+  #[serde(default = "default_cache_ttl_secs")]
+  pub ttl_secs: u64,
+  /// Only serve cached responses, nothing is sent to phabricator.
+  #[serde(default)]
+  pub offline: bool,
+}
+
+impl Default for CacheConfig {
+  fn default() -> CacheConfig {
+    return CacheConfig {
+      path: None,
+      ttl_secs: default_cache_ttl_secs(),
+      offline: false,
+    };
+  }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct PhabricatorClientConfig {
   pub host: String,
   pub api_token: String,
   pub cert_identity_config: Option<CertIdentityConfig>,
+  /// Maximum number of conduit requests that are sent at the same time
+  /// when fetching a task tree.
+  #[serde(default = "default_max_concurrency")]
+  pub max_concurrency: usize,
+  #[serde(default)]
+  pub retry_config: RetryConfig,
+  #[serde(default = "default_connect_timeout_secs")]
+  pub connect_timeout_secs: u64,
+  /// Timeout of a single request attempt, from sending it until the whole response is read.
+  #[serde(default = "default_request_timeout_secs")]
+  pub request_timeout_secs: u64,
+  /// Requests are not limited if not set.
+  pub rate_limit_config: Option<RateLimitConfig>,
+  /// Responses are not cached if not set.
+  pub cache_config: Option<CacheConfig>,
+}
+
+pub(crate) fn default_max_concurrency() -> usize {
+  return 4;
+}
+
+fn default_cache_ttl_secs() -> u64 {
+  return 300;
+}
+
+fn default_connect_timeout_secs() -> u64 {
+  return 10;
+}
+
+fn default_request_timeout_secs() -> u64 {
+  return 60;
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_backoff_grows_exponentially_until_max_backoff() {
+    let retry_config = RetryConfig {
+      max_attempts: 10,
+      initial_backoff_ms: 100,
+      max_backoff_ms: 1000,
+      retry_on_statuses: vec![],
+    };
+
+    let backoff_ms = |attempt| retry_config.backoff(attempt).as_millis();
+
+    assert!((50..=100).contains(&backoff_ms(1)));
+    assert!((200..=400).contains(&backoff_ms(3)));
+    assert!((500..=1000).contains(&backoff_ms(8)));
+    assert!((500..=1000).contains(&backoff_ms(100)));
+  }
+
+  #[test]
+  fn test_retry_after_is_capped_to_max_backoff() {
+    let retry_config = RetryConfig {
+      max_backoff_ms: 1000,
+      ..RetryConfig::default()
+    };
+
+    assert_eq!(
+      retry_config.retry_after(Duration::from_millis(500)),
+      Duration::from_millis(500)
+    );
+    assert_eq!(
+      retry_config.retry_after(Duration::from_secs(3600)),
+      Duration::from_millis(1000)
+    );
+  }
 }
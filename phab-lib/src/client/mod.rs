@@ -1,2 +1,10 @@
+pub mod cache;
+pub mod cassette;
 pub mod config;
 pub mod phabricator;
+mod rate_limiter;
+pub mod revision_query;
+pub mod task_edit;
+pub mod task_query;
+mod task_tree;
+pub mod transport;
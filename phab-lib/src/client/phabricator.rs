@@ -1,26 +1,49 @@
-use std::fs;
-
-use anyhow::Error;
-use futures::future;
-use futures::future::BoxFuture;
-use futures::future::FutureExt;
-use reqwest::Client as HttpClient;
-use reqwest::ClientBuilder as HttpClientBuilder;
-use reqwest::Identity;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::Mutex;
+
+use futures::stream;
+use futures::stream::BoxStream;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
+use serde_json::json;
 use serde_json::Value;
 
+use crate::client::cache::CachingTransport;
+use crate::client::config::default_max_concurrency;
 use crate::client::config::PhabricatorClientConfig;
+use crate::client::revision_query::RevisionQuery;
+use crate::client::task_edit::TaskTransaction;
+use crate::client::task_query::TaskQuery;
+use crate::client::task_tree::TaskTree;
+use crate::client::transport::ConduitTransport;
+use crate::client::transport::HttpTransport;
+use crate::dto::Column;
+use crate::dto::Comment;
+use crate::dto::Commit;
+use crate::dto::Edge;
+use crate::dto::ParseError;
+use crate::dto::Project;
+use crate::dto::Revision;
 use crate::dto::Task;
 use crate::dto::TaskFamily;
 use crate::dto::User;
+use crate::dto::Workboard;
+use crate::dto::WorkboardLane;
 use crate::types::ResultAnyError;
+use crate::utils::fuzzy::fuzzy_match;
 
 pub struct PhabricatorClient {
-  http: HttpClient,
-  host: String,
-  api_token: String,
+  transport: Box<dyn ConduitTransport>,
+  max_concurrency: usize,
+  users_by_phid: Mutex<HashMap<String, User>>,
 }
 
+/// Number of ids that we put in a single search constraint,
+/// bigger list will be split and fetched concurrently.
+pub(crate) const SEARCH_CONSTRAINT_CHUNK_SIZE: usize = 100;
+
 #[derive(Debug, Clone, thiserror::Error)]
 pub enum ErrorType {
   #[error("Certificate identity path: {pkcs12_path:?}, error: {message:?}")]
@@ -43,6 +66,57 @@ pub enum ErrorType {
 
   #[error("Parse error: {message}")]
   ParseError { message: String },
+
+  #[error("Edit task error: {message}")]
+  EditTaskError { message: String },
+
+  /// Conduit responds with http 200 even for failures,
+  /// the failure is described by `error_code` and `error_info`.
+  #[error("{code}: {info}")]
+  ConduitError {
+    code: String,
+    info: String,
+    method: String,
+  },
+}
+
+/// Flatten json value into conduit form fields, nested values are
+/// encoded the way PHP parses them, e.g. `transactions[0][value][1]`.
+/// ```
+/// # use serde_json::json;
+/// # use phab_lib::client::phabricator::form_fields_from_json;
+///
+/// let fields = form_fields_from_json("transactions", &json!([{ "type": "title", "value": "foo" }]));
+///
+/// assert_eq!(fields, vec![
+///   ("transactions[0][type]".to_owned(), "title".to_owned()),
+///   ("transactions[0][value]".to_owned(), "foo".to_owned()),
+/// ]);
+/// ```
+pub fn form_fields_from_json(key: &str, value: &Value) -> Vec<(String, String)> {
+  return match value {
+    Value::Null => vec![(key.to_owned(), String::new())],
+    Value::String(s) => vec![(key.to_owned(), s.clone())],
+    Value::Bool(b) => vec![(key.to_owned(), b.to_string())],
+    Value::Number(n) => vec![(key.to_owned(), n.to_string())],
+    Value::Array(values) => values
+      .iter()
+      .enumerate()
+      .flat_map(|(i, v)| form_fields_from_json(&format!("{}[{}]", key, i), v))
+      .collect(),
+    Value::Object(map) => map
+      .iter()
+      .flat_map(|(k, v)| {
+        let key = if key.is_empty() {
+          k.clone()
+        } else {
+          format!("{}[{}]", key, k)
+        };
+
+        return form_fields_from_json(&key, v);
+      })
+      .collect(),
+  };
 }
 
 impl PhabricatorClient {
@@ -59,313 +133,996 @@ impl PhabricatorClient {
     return id.trim_start_matches('T');
   }
 
+  /// Same as `clean_id` but for differential revision id e.g. D1234.
+  /// ```
+  /// # use phab_lib::client::phabricator::PhabricatorClient;
+  ///
+  /// let revision_id  = PhabricatorClient::clean_revision_id("D1234");
+  /// assert_eq!(revision_id, "1234");
+  /// ```
+  pub fn clean_revision_id(id: &str) -> &str {
+    return id.trim_start_matches('D');
+  }
+
   pub fn new(config: PhabricatorClientConfig) -> ResultAnyError<PhabricatorClient> {
-    let mut http_client_builder = Ok(HttpClientBuilder::new());
-    let PhabricatorClientConfig {
-      host,
-      api_token,
-      cert_identity_config,
-    } = config;
-
-    let cert_identity: Option<Result<_, _>> = cert_identity_config.map(|config| {
-      return fs::read(&config.pkcs12_path)
-        .map_err(|err| ErrorType::FailToConfigureHttpClient {
-          message: format!("Failed to read pkcs12 from {}, {}", config.pkcs12_path, err),
-        })
-        .and_then(|bytes| {
-          return Identity::from_pkcs12_der(&bytes, &config.pkcs12_password).map_err(|err| {
-            ErrorType::CertificateIdentityError {
-              pkcs12_path: String::from(config.pkcs12_path),
-              message: err.to_string(),
-            }
+    let max_concurrency = config.max_concurrency;
+    let cache_config = config.cache_config.clone();
+    let host = config.host.clone();
+    let api_token = config.api_token.clone();
+    let transport = HttpTransport::new(config)?;
+
+    let phabricator = match cache_config {
+      Some(cache_config) => PhabricatorClient::with_transport(CachingTransport::new(
+        transport,
+        &cache_config,
+        &host,
+        &api_token,
+      )?),
+      None => PhabricatorClient::with_transport(transport),
+    };
+
+    return Ok(phabricator.with_max_concurrency(max_concurrency));
+  }
+
+  /// Client on top of a custom transport, e.g. [FakeTransport](crate::client::transport::FakeTransport)
+  /// to use the client without a phabricator server.
+  pub fn with_transport(transport: impl ConduitTransport + 'static) -> PhabricatorClient {
+    return PhabricatorClient {
+      transport: Box::new(transport),
+      max_concurrency: default_max_concurrency(),
+      users_by_phid: Mutex::new(HashMap::new()),
+    };
+  }
+
+  /// Maximum number of conduit requests that are sent at the same time
+  /// when fetching a task tree.
+  pub fn with_max_concurrency(mut self, max_concurrency: usize) -> PhabricatorClient {
+    // Zero concurrency would never make progress
+    self.max_concurrency = max_concurrency.max(1);
+    return self;
+  }
+}
+
+impl PhabricatorClient {
+  /// Call conduit `method` and return the `result` part of the response body.
+  async fn call(&self, method: &str, params: &Value) -> ResultAnyError<Value> {
+    return self.transport.call(method, params).await;
+  }
+
+  /// Call a conduit search `method` (e.g. `maniphest.search`) and stream
+  /// every result item, following `result.cursor.after` until the last page.
+  pub fn search_stream<'a>(
+    &'a self,
+    method: &'a str,
+    params: Value,
+  ) -> BoxStream<'a, ResultAnyError<Value>> {
+    // State is the cursor of the next page to fetch, `None` means there's no next page.
+    let initial_state = Some(Value::Null);
+
+    return stream::try_unfold(initial_state, move |after| {
+      let params = params.clone();
+
+      return async move {
+        let after = match after {
+          None => return Ok(None),
+          Some(after) => after,
+        };
+
+        return self
+          .search_page(method, params, after)
+          .await
+          .map(|(items, next_after)| {
+            return Some((stream::iter(items.into_iter().map(Ok)), next_after));
           });
-        });
+      };
+    })
+    .try_flatten()
+    .boxed();
+  }
+
+  /// Fetch a single page, returns the page items and the cursor of the next page.
+  async fn search_page(
+    &self,
+    method: &str,
+    mut params: Value,
+    after: Value,
+  ) -> ResultAnyError<(Vec<Value>, Option<Value>)> {
+    if !after.is_null() {
+      params["after"] = after;
+    }
+
+    let mut result = self.call(method, &params).await?;
+
+    let items = match result["data"].take() {
+      Value::Array(items) => items,
+      _ => {
+        return Err(
+          ErrorType::ParseError {
+            message: format!("Cannot parse {} result {}", method, &result),
+          }
+          .into(),
+        );
+      }
+    };
+
+    let next_after = match result["cursor"]["after"].take() {
+      Value::Null => None,
+      after => Some(after),
+    };
+
+    return Ok((items, next_after));
+  }
+
+  /// Same as `search_stream` but collects all result items.
+  pub async fn search(&self, method: &str, params: Value) -> ResultAnyError<Vec<Value>> {
+    return self.search_stream(method, params).try_collect().await;
+  }
+
+  /// Get the user that owns the api token.
+  pub async fn get_current_user(&self) -> ResultAnyError<User> {
+    let result = self.call("user.whoami", &json!({})).await?;
+
+    let user_phid = result["phid"].as_str().ok_or_else(|| {
+      return ErrorType::ParseError {
+        message: format!("Cannot parse {}", &result),
+      };
+    })?;
+
+    return self.get_user_by_phid(user_phid).await?.ok_or_else(|| {
+      return ErrorType::ParseError {
+        message: format!("Could not find current user {}", user_phid),
+      }
+      .into();
     });
+  }
 
-    if let Some(cert_identity) = cert_identity {
-      http_client_builder =
-        http_client_builder.and_then(|http_client_builder: HttpClientBuilder| {
-          return cert_identity.map(|cert_identity: Identity| {
-            return http_client_builder.identity(cert_identity);
-          });
-        });
+  pub async fn get_users_by_usernames(&self, usernames: Vec<&str>) -> ResultAnyError<Vec<User>> {
+    // Empty constraint means no constraint at all for conduit
+    if usernames.is_empty() {
+      return Ok(vec![]);
     }
 
-    return http_client_builder
-      .and_then(|http_client_builder| {
-        http_client_builder
-          .build()
-          .map_err(|err| ErrorType::FailToConfigureHttpClient {
-            message: err.to_string(),
-          })
-      })
-      .map_err(Error::new)
-      .map(|http_client| {
-        return PhabricatorClient {
-          http: http_client,
-          host: String::from(host),
-          api_token: String::from(api_token),
+    let params = json!({
+      "constraints": {
+        "usernames": usernames,
+      },
+    });
+
+    let users_json = self.search("user.search", params).await?;
+
+    return parse_all(&users_json, User::from_json);
+  }
+
+  /// Search projects matching the given name, conduit does a fuzzy
+  /// name match so the result might contain more than 1 project.
+  pub async fn search_projects_by_name(&self, name: &str) -> ResultAnyError<Vec<Project>> {
+    let params = json!({
+      "constraints": {
+        "name": name,
+      },
+    });
+
+    let projects_json = self.search("project.search", params).await?;
+
+    return parse_all(&projects_json, Project::from_json);
+  }
+
+  /// Get workboard columns of the given projects.
+  pub async fn get_project_columns(&self, project_phids: Vec<&str>) -> ResultAnyError<Vec<Column>> {
+    // Empty constraint means no constraint at all for conduit
+    if project_phids.is_empty() {
+      return Ok(vec![]);
+    }
+
+    let params = json!({
+      "constraints": {
+        "projects": project_phids,
+      },
+    });
+
+    let columns_json = self.search("project.column.search", params).await?;
+
+    return parse_all(&columns_json, Column::from_json);
+  }
+
+  pub async fn get_projects_by_phids(
+    &self,
+    project_phids: Vec<&str>,
+  ) -> ResultAnyError<Vec<Project>> {
+    // Empty constraint means no constraint at all for conduit
+    if project_phids.is_empty() {
+      return Ok(vec![]);
+    }
+
+    let params = json!({
+      "constraints": {
+        "phids": project_phids,
+      },
+    });
+
+    let projects_json = self.search("project.search", params).await?;
+
+    return parse_all(&projects_json, Project::from_json);
+  }
+
+  pub async fn get_columns_by_phids(&self, column_phids: Vec<&str>) -> ResultAnyError<Vec<Column>> {
+    // Empty constraint means no constraint at all for conduit
+    if column_phids.is_empty() {
+      return Ok(vec![]);
+    }
+
+    let params = json!({
+      "constraints": {
+        "phids": column_phids,
+      },
+    });
+
+    let columns_json = self.search("project.column.search", params).await?;
+
+    return parse_all(&columns_json, Column::from_json);
+  }
+
+  /// Get the project workboard with the tasks in every visible column,
+  /// closed tasks are only included if `include_closed` is set.
+  pub async fn get_workboard(
+    &self,
+    project_phid: &str,
+    include_closed: bool,
+  ) -> ResultAnyError<Workboard> {
+    let project = self
+      .get_projects_by_phids(vec![project_phid])
+      .await?
+      .pop()
+      .ok_or_else(|| {
+        return ErrorType::ValidationError {
+          message: format!("Could not find project {}", project_phid),
         };
-      });
+      })?;
+
+    let columns: Vec<Column> = self
+      .get_project_columns(vec![project_phid])
+      .await?
+      .into_iter()
+      .filter(|column| !column.is_hidden)
+      .collect();
+
+    let column_phids: Vec<&str> = columns.iter().map(|column| column.phid.as_str()).collect();
+
+    let mut tasks = if column_phids.is_empty() {
+      vec![]
+    } else {
+      let query = TaskQuery::new()
+        .column_phids(column_phids)
+        .order("priority");
+      let query = if include_closed {
+        query
+      } else {
+        query.query_key("open")
+      };
+
+      self.search_tasks(&query).await?
+    };
+
+    self.fetch_tasks_users(tasks.iter_mut().collect()).await?;
+
+    let lanes = columns
+      .into_iter()
+      .map(|column| {
+        let tasks = tasks
+          .iter()
+          .filter(|task| task.boards.iter().any(|board| board.phid == column.phid))
+          .cloned()
+          .collect();
+
+        return WorkboardLane { column, tasks };
+      })
+      .collect();
+
+    return Ok(Workboard { project, lanes });
   }
-}
 
-impl PhabricatorClient {
   pub async fn get_user_by_phid(&self, user_phid: &str) -> ResultAnyError<Option<User>> {
     return self
       .get_users_by_phids(vec![user_phid])
       .await
-      .map(|users| users.get(0).map(ToOwned::to_owned));
+      .map(|users| users.first().map(ToOwned::to_owned));
   }
 
   pub async fn get_task_by_id(&self, task_id: &str) -> ResultAnyError<Option<Task>> {
     return self
       .get_tasks_by_ids(vec![task_id])
       .await
-      .map(|tasks| tasks.get(0).map(ToOwned::to_owned));
+      .map(|tasks| tasks.first().map(ToOwned::to_owned));
   }
 
+  /// Users are cached by phid for the lifetime of the client,
+  /// only phids that were never fetched are sent to conduit.
   pub async fn get_users_by_phids(&self, user_phids: Vec<&str>) -> ResultAnyError<Vec<User>> {
-    let mut form: Vec<(String, &str)> = vec![("api.token".to_owned(), self.api_token.as_str())];
+    let mut user_phids = user_phids;
+    let mut seen_phids = HashSet::new();
 
-    for i in 0..user_phids.len() {
-      let key = format!("constraints[phids][{}]", i);
-      let user_phid = user_phids.get(i).unwrap();
+    user_phids.retain(|phid| seen_phids.insert(*phid));
 
-      form.push((key, user_phid));
-    }
+    let missing_phids: Vec<&str> = {
+      let users_by_phid = self.users_by_phid.lock().unwrap();
 
-    let url = format!("{}/api/user.search", self.host);
+      user_phids
+        .iter()
+        .filter(|phid| !users_by_phid.contains_key(**phid))
+        .copied()
+        .collect()
+    };
 
-    log::debug!("Getting user by id {} {:?}", url, form);
+    // Empty constraint means no constraint at all for conduit
+    if !missing_phids.is_empty() {
+      let params = json!({
+        "constraints": {
+          "phids": missing_phids,
+        },
+      });
 
-    let result = self
-      .http
-      .post(&url)
-      .form(&form)
-      .send()
-      .await
-      .map_err(Error::new)?;
+      log::debug!("Getting users by phids {:?}", missing_phids);
 
-    let response_text = result.text().await.map_err(Error::new)?;
+      let users_json = self.search("user.search", params).await?;
+      let users: Vec<User> = parse_all(&users_json, User::from_json)?;
 
-    log::debug!("Response {}", response_text);
+      log::debug!("Parsed {:?}", users);
 
-    let body: Value = serde_json::from_str(response_text.as_str()).map_err(Error::new)?;
+      let mut users_by_phid = self.users_by_phid.lock().unwrap();
 
-    if let Value::Array(users_json) = &body["result"]["data"] {
-      if users_json.is_empty() {
-        return Ok(vec![]);
+      for user in users {
+        users_by_phid.insert(user.phid.clone(), user);
       }
+    }
 
-      log::debug!("Parsing {:?}", users_json);
+    let users_by_phid = self.users_by_phid.lock().unwrap();
 
-      // We only have 1 possible assignment
-      let users: Vec<User> = users_json.iter().map(User::from_json).collect();
+    return Ok(
+      user_phids
+        .iter()
+        .filter_map(|phid| users_by_phid.get(*phid).cloned())
+        .collect(),
+    );
+  }
 
-      log::debug!("Parsed {:?}", users);
+  /// Resolve author and owner of the given tasks with a single `user.search` call.
+  pub async fn fetch_tasks_users(&self, tasks: Vec<&mut Task>) -> ResultAnyError<()> {
+    let user_phids: Vec<String> = tasks
+      .iter()
+      .flat_map(|task| {
+        return std::iter::once(task.author_phid.clone()).chain(task.assigned_phid.clone());
+      })
+      .collect();
 
-      return Ok(users);
-    } else {
-      return Err(
-        ErrorType::ParseError {
-          message: format!("Cannot parse {}", &body),
-        }
-        .into(),
-      );
+    let users = self
+      .get_users_by_phids(user_phids.iter().map(String::as_str).collect())
+      .await?;
+
+    let user_of = |phid: &str| users.iter().find(|user| user.phid == phid).cloned();
+
+    for task in tasks {
+      task.author = user_of(&task.author_phid);
+      task.assigned = task.assigned_phid.as_deref().and_then(user_of);
     }
+
+    return Ok(());
   }
 
-  pub async fn get_tasks_by_ids(&self, task_ids: Vec<&str>) -> ResultAnyError<Vec<Task>> {
-    let mut form: Vec<(String, &str)> = vec![
-      ("api.token".to_owned(), self.api_token.as_str()),
-      ("order".to_owned(), "oldest"),
-      ("attachments[columns]".to_owned(), "true"),
-      ("attachments[projects]".to_owned(), "true"),
-    ];
+  /// Resolve projects of the given tasks with a single `project.search` call.
+  pub async fn fetch_tasks_projects(&self, tasks: Vec<&mut Task>) -> ResultAnyError<()> {
+    let mut project_phids: Vec<&str> = tasks
+      .iter()
+      .flat_map(|task| task.project_phids.iter().map(String::as_str))
+      .collect();
+
+    project_phids.sort_unstable();
+    project_phids.dedup();
+
+    let projects = self.get_projects_by_phids(project_phids).await?;
+
+    for task in tasks {
+      task.projects = task
+        .project_phids
+        .iter()
+        .filter_map(|phid| projects.iter().find(|project| &project.phid == phid))
+        .cloned()
+        .collect();
+    }
 
-    for i in 0..task_ids.len() {
-      let key = format!("constraints[ids][{}]", i);
-      let task_id = PhabricatorClient::clean_id(task_ids.get(i).unwrap());
+    return Ok(());
+  }
+
+  /// Resolve projects of every task in the family tree.
+  pub async fn fetch_task_family_projects(
+    &self,
+    task_family: &mut TaskFamily,
+  ) -> ResultAnyError<()> {
+    return self.fetch_tasks_projects(task_family.tasks_mut()).await;
+  }
 
-      form.push((key, task_id));
+  /// Resolve author and owner of every task in the family tree.
+  pub async fn fetch_task_family_users(&self, task_family: &mut TaskFamily) -> ResultAnyError<()> {
+    return self.fetch_tasks_users(task_family.tasks_mut()).await;
+  }
+
+  pub async fn get_tasks_by_ids(&self, task_ids: Vec<&str>) -> ResultAnyError<Vec<Task>> {
+    // Empty constraint means no constraint at all for conduit
+    if task_ids.is_empty() {
+      return Ok(vec![]);
     }
 
-    let url = format!("{}/api/maniphest.search", self.host);
+    let task_ids: Vec<&str> = task_ids
+      .into_iter()
+      .map(PhabricatorClient::clean_id)
+      .collect();
 
-    log::debug!("Getting task by id {} {:?}", url, form);
+    log::debug!("Getting tasks by ids {:?}", task_ids);
 
-    let result = self
-      .http
-      .post(&url)
-      .form(&form)
-      .send()
-      .await
-      .map_err(Error::new)?;
+    return self.search_tasks(&TaskQuery::new().ids(task_ids)).await;
+  }
 
-    let response_text = result.text().await.map_err(Error::new)?;
+  /// Get tasks by phids, phids are searched in constraint sized chunks
+  /// so any number of phids can be given.
+  pub async fn get_tasks_by_phids(&self, task_phids: Vec<&str>) -> ResultAnyError<Vec<Task>> {
+    log::debug!("Getting tasks by phids {:?}", task_phids);
 
-    log::debug!("Response {}", response_text);
+    return self
+      .fetch_chunked(task_phids, |task_phids| async move {
+        // Empty constraint means no constraint at all for conduit
+        if task_phids.is_empty() {
+          return Ok(vec![]);
+        }
 
-    let body: Value = serde_json::from_str(response_text.as_str()).map_err(Error::new)?;
+        return self.search_tasks(&TaskQuery::new().phids(task_phids)).await;
+      })
+      .await;
+  }
 
-    if let Value::Array(tasks_json) = &body["result"]["data"] {
-      if tasks_json.is_empty() {
-        return Ok(vec![]);
-      }
+  /// Search tasks matching the given query, result will be paginated
+  /// until `query.limit` or all matching tasks are fetched.
+  pub async fn search_tasks(&self, query: &TaskQuery) -> ResultAnyError<Vec<Task>> {
+    let tasks_stream = self.search_stream("maniphest.search", query.to_params());
+
+    let tasks_json: Vec<Value> = match query.limit {
+      Some(limit) => tasks_stream.take(limit).try_collect().await?,
+      None => tasks_stream.try_collect().await?,
+    };
 
-      log::debug!("Parsing {:?}", tasks_json);
+    let tasks: Vec<Task> = parse_all(&tasks_json, Task::from_json)?;
 
-      // We only have 1 possible assignment
-      let tasks: Vec<Task> = tasks_json.iter().map(Task::from_json).collect();
+    log::debug!("Parsed {:?}", tasks);
 
-      log::debug!("Parsed {:?}", tasks);
+    return Ok(tasks);
+  }
 
-      return Ok(tasks);
-    } else {
+  pub async fn get_revision_by_id(&self, revision_id: &str) -> ResultAnyError<Option<Revision>> {
+    let revision_id = PhabricatorClient::clean_revision_id(revision_id);
+
+    return self
+      .search_revisions(&RevisionQuery::new().ids(vec![revision_id]))
+      .await
+      .map(|revisions| revisions.first().map(ToOwned::to_owned));
+  }
+
+  /// Search revisions matching the given query, result will be paginated
+  /// until `query.limit` or all matching revisions are fetched.
+  pub async fn search_revisions(&self, query: &RevisionQuery) -> ResultAnyError<Vec<Revision>> {
+    let revisions_stream = self.search_stream("differential.revision.search", query.to_params());
+
+    let revisions_json: Vec<Value> = match query.limit {
+      Some(limit) => revisions_stream.take(limit).try_collect().await?,
+      None => revisions_stream.try_collect().await?,
+    };
+
+    return parse_all(&revisions_json, Revision::from_json);
+  }
+
+  /// Create a new task by applying the given transactions,
+  /// at least a `TaskTransaction::Title` is required by conduit.
+  pub async fn create_task(&self, transactions: Vec<TaskTransaction>) -> ResultAnyError<Task> {
+    return self.apply_task_transactions(None, transactions).await;
+  }
+
+  pub async fn edit_task(
+    &self,
+    task_id: &str,
+    transactions: Vec<TaskTransaction>,
+  ) -> ResultAnyError<Task> {
+    return self
+      .apply_task_transactions(Some(PhabricatorClient::clean_id(task_id)), transactions)
+      .await;
+  }
+
+  async fn apply_task_transactions(
+    &self,
+    object_identifier: Option<&str>,
+    transactions: Vec<TaskTransaction>,
+  ) -> ResultAnyError<Task> {
+    if transactions.is_empty() {
       return Err(
-        ErrorType::ParseError {
-          message: format!("Cannot parse {}", &body),
+        ErrorType::ValidationError {
+          message: String::from("Transactions cannot be empty"),
         }
         .into(),
       );
     }
+
+    let transactions: Vec<Value> = transactions.iter().map(TaskTransaction::to_json).collect();
+    let mut params = json!({ "transactions": transactions });
+
+    if let Some(object_identifier) = object_identifier {
+      params["objectIdentifier"] = json!(object_identifier);
+    }
+
+    let result = self.call("maniphest.edit", &params).await?;
+
+    let task_id = result["object"]["id"].as_u64().ok_or_else(|| {
+      return ErrorType::EditTaskError {
+        message: format!("Cannot parse {}", &result),
+      };
+    })?;
+
+    // maniphest.edit only returns the object id and phid,
+    // so we need to refetch to get the updated task.
+    let task_id = format!("{}", task_id);
+
+    return self.get_task_by_id(&task_id).await?.ok_or_else(|| {
+      return ErrorType::FetchTaskError {
+        message: format!("Could not find edited task {}", task_id),
+      }
+      .into();
+    });
   }
 
-  pub async fn get_task_family(&self, root_task_id: &str) -> ResultAnyError<Option<TaskFamily>> {
-    let parent_task = self.get_task_by_id(root_task_id).await?;
+  /// Get comments of the given task ordered from the oldest one,
+  /// comment authors will be resolved as well.
+  pub async fn get_task_comments(&self, task_id: &str) -> ResultAnyError<Vec<Comment>> {
+    let params = json!({
+      "objectIdentifier": format!("T{}", PhabricatorClient::clean_id(task_id)),
+    });
 
-    if parent_task.is_none() {
-      return Ok(None);
+    let transactions_json = self.search("transaction.search", params).await?;
+
+    // Conduit returns the newest transaction first
+    let mut comments: Vec<Comment> = parse_all(&transactions_json, Comment::from_transaction_json)?
+      .into_iter()
+      .rev()
+      .flatten()
+      .collect();
+
+    let mut author_phids: Vec<&str> = comments
+      .iter()
+      .map(|comment| comment.author_phid.as_str())
+      .collect();
+
+    author_phids.sort_unstable();
+    author_phids.dedup();
+
+    if author_phids.is_empty() {
+      return Ok(comments);
     }
 
-    let parent_task = parent_task.unwrap();
+    let authors = self.get_users_by_phids(author_phids).await?;
 
-    let child_tasks = self.get_child_tasks(vec![root_task_id]).await?;
-    let task_family = TaskFamily {
-      parent_task,
-      children: child_tasks,
-    };
+    for comment in comments.iter_mut() {
+      comment.author = authors
+        .iter()
+        .find(|user| user.phid == comment.author_phid)
+        .cloned();
+    }
 
-    return Ok(Some(task_family));
+    return Ok(comments);
   }
 
-  pub fn get_child_tasks<'a>(
-    &'a self,
-    parent_task_ids: Vec<&'a str>,
-  ) -> BoxFuture<'a, ResultAnyError<Vec<TaskFamily>>> {
-    return async move {
-      if parent_task_ids.is_empty() {
+  pub async fn add_task_comment(&self, task_id: &str, comment: &str) -> ResultAnyError<Task> {
+    return self
+      .edit_task(task_id, vec![TaskTransaction::Comment(comment.to_owned())])
+      .await;
+  }
+
+  /// Move the task to the workboard column matching `column_name`, columns are matched
+  /// with [`fuzzy_match`] on every board of the task's projects. `board_name` picks the
+  /// project board when the column name matches columns on several boards.
+  pub async fn move_task_to_column(
+    &self,
+    task_id: &str,
+    column_name: &str,
+    board_name: Option<&str>,
+  ) -> ResultAnyError<Task> {
+    let task = self.get_task_by_id(task_id).await?.ok_or_else(|| {
+      return ErrorType::FetchTaskError {
+        message: format!("Could not find task {}", task_id),
+      };
+    })?;
+
+    let task_projects = self
+      .get_projects_by_phids(task.project_phids.iter().map(String::as_str).collect())
+      .await?;
+
+    let projects: Vec<&Project> = match board_name {
+      Some(board_name) => fuzzy_match(&task_projects, board_name, |project| &project.name),
+      None => task_projects.iter().collect(),
+    };
+
+    let project_names = |projects: &[&Project]| -> String {
+      return projects
+        .iter()
+        .map(|project| project.name.as_str())
+        .collect::<Vec<&str>>()
+        .join(", ");
+    };
+
+    if let (Some(board_name), [_, _, ..]) = (board_name, projects.as_slice()) {
+      return Err(
+        ErrorType::ValidationError {
+          message: format!(
+            "Board name {} is ambiguous, matching boards: {}",
+            board_name,
+            project_names(&projects)
+          ),
+        }
+        .into(),
+      );
+    }
+
+    let columns: Vec<Column> = self
+      .get_project_columns(
+        projects
+          .iter()
+          .map(|project| project.phid.as_str())
+          .collect(),
+      )
+      .await?
+      .into_iter()
+      .filter(|column| !column.is_hidden)
+      .collect();
+
+    let column_label = |column: &Column| -> String {
+      let project_name = task_projects
+        .iter()
+        .find(|project| project.phid == column.project_phid)
+        .map(|project| project.name.as_str())
+        .unwrap_or(&column.project_phid);
+
+      return format!("{}: {}", project_name, column.name);
+    };
+
+    let column_labels = |columns: &[&Column]| -> String {
+      return columns
+        .iter()
+        .map(|column| column_label(column))
+        .collect::<Vec<String>>()
+        .join(", ");
+    };
+
+    if columns.is_empty() {
+      let message = match board_name {
+        Some(board_name) => format!(
+          "T{} is not on board {}, task boards: {}",
+          task.id,
+          board_name,
+          project_names(&task_projects.iter().collect::<Vec<&Project>>())
+        ),
+        None => format!("T{} is not on any workboard", task.id),
+      };
+
+      return Err(ErrorType::ValidationError { message }.into());
+    }
+
+    let column = match fuzzy_match(&columns, column_name, |column| &column.name).as_slice() {
+      [column] => *column,
+      [] => {
         return Err(
           ErrorType::ValidationError {
-            message: String::from("Parent ids cannot be empty"),
+            message: format!(
+              "Could not find column {}, valid columns: {}",
+              column_name,
+              column_labels(&columns.iter().collect::<Vec<&Column>>())
+            ),
           }
           .into(),
-        );
+        )
       }
+      matches => {
+        return Err(
+          ErrorType::ValidationError {
+            message: format!(
+              "Column name {} is ambiguous, matching columns: {}",
+              column_name,
+              column_labels(matches)
+            ),
+          }
+          .into(),
+        )
+      }
+    };
 
-      let mut form: Vec<(String, &str)> = vec![("api.token".to_owned(), self.api_token.as_str())];
+    return self
+      .edit_task(&task.id, vec![TaskTransaction::Column(column.phid.clone())])
+      .await;
+  }
 
-      for i in 0..parent_task_ids.len() {
-        let task_id = PhabricatorClient::clean_id(parent_task_ids.get(i).unwrap());
-        let key = format!("constraints[parentIDs][{}]", i);
+  /// Get edges of the given source objects, e.g. `task.revision` edges
+  /// of a task will be its attached revisions.
+  /// Get edges of the given source objects, sources are searched
+  /// in constraint sized chunks so any number of phids can be given.
+  pub async fn get_edges(
+    &self,
+    source_phids: Vec<&str>,
+    edge_types: Vec<&str>,
+  ) -> ResultAnyError<Vec<Edge>> {
+    let edge_types = &edge_types;
 
-        form.push((key, task_id));
-      }
+    return self
+      .fetch_chunked(source_phids, |source_phids| {
+        return self.search_edges(source_phids, edge_types);
+      })
+      .await;
+  }
 
-      form.push(("order".to_owned(), "oldest"));
-      form.push(("attachments[columns]".to_owned(), "true"));
-      form.push(("attachments[projects]".to_owned(), "true"));
+  async fn search_edges(
+    &self,
+    source_phids: Vec<&str>,
+    edge_types: &[&str],
+  ) -> ResultAnyError<Vec<Edge>> {
+    // Empty constraint means no constraint at all for conduit
+    if source_phids.is_empty() {
+      return Ok(vec![]);
+    }
 
-      let url = format!("{}/api/maniphest.search", self.host);
+    let params = json!({
+      "sourcePHIDs": source_phids,
+      "types": edge_types,
+    });
 
-      log::debug!("Getting tasks {} {:?}", url, form);
+    let edges_json = self.search("edge.search", params).await?;
 
-      let result = self
-        .http
-        .post(&url)
-        .form(&form)
-        .send()
-        .await
-        .map_err(Error::new)?;
+    return parse_all(&edges_json, Edge::from_json);
+  }
 
-      let response_text = result.text().await.map_err(Error::new)?;
+  pub async fn get_commits_by_phids(&self, commit_phids: Vec<&str>) -> ResultAnyError<Vec<Commit>> {
+    // Empty constraint means no constraint at all for conduit
+    if commit_phids.is_empty() {
+      return Ok(vec![]);
+    }
 
-      log::debug!("Response {}", response_text);
+    let params = json!({
+      "constraints": {
+        "phids": commit_phids,
+      },
+    });
 
-      let body: Value = serde_json::from_str(response_text.as_str()).map_err(Error::new)?;
+    let commits_json = self.search("diffusion.commit.search", params).await?;
 
-      if let Value::Array(tasks_json) = &body["result"]["data"] {
-        let tasks: Vec<BoxFuture<ResultAnyError<TaskFamily>>> = tasks_json
-          .iter()
-          .map(|v: &Value| -> BoxFuture<ResultAnyError<TaskFamily>> {
-            return async move {
-              let parent_task = Task::from_json(&v);
-
-              let children = self
-                .get_child_tasks(vec![parent_task.id.as_str()])
-                .await
-                .map_err(|err| {
-                  return ErrorType::FetchSubTasksError {
-                    message: format!(
-                      "Could not fetch sub tasks with parent id {}, err: {}",
-                      parent_task.id, err
-                    ),
-                  };
-                })?;
-
-              return Ok(TaskFamily {
-                parent_task,
-                children,
-              });
-            }
-            .boxed();
-          })
-          .collect();
+    return parse_all(&commits_json, Commit::from_json);
+  }
 
-        let (tasks, failed_tasks): (Vec<_>, Vec<_>) = future::join_all(tasks)
-          .await
-          .into_iter()
-          .partition(Result::is_ok);
-
-        if !failed_tasks.is_empty() {
-          let error = ErrorType::FetchSubTasksError {
-            message: failed_tasks
-              .into_iter()
-              .fold(String::new(), |acc, task_result| {
-                return format!("{}\n{}", acc, task_result.err().unwrap());
-              }),
-          };
-
-          return Err(error.into());
+  /// Fetch revisions and commits attached to every task in the family tree,
+  /// edges of the whole tree are fetched in constraint sized chunks instead of per task.
+  pub async fn fetch_task_family_related_objects(
+    &self,
+    task_family: &mut TaskFamily,
+  ) -> ResultAnyError<()> {
+    let task_phids: Vec<&str> = task_family
+      .tasks()
+      .into_iter()
+      .map(|task| task.phid.as_str())
+      .collect();
+
+    let edges = self
+      .get_edges(task_phids, vec!["task.revision", "task.commit"])
+      .await?;
+
+    let phids_of = |edge_type: &str| -> Vec<&str> {
+      let mut phids: Vec<&str> = edges
+        .iter()
+        .filter(|edge| edge.edge_type == edge_type)
+        .map(|edge| edge.destination_phid.as_str())
+        .collect();
+
+      phids.sort_unstable();
+      phids.dedup();
+
+      return phids;
+    };
+
+    let revisions = self
+      .fetch_chunked(phids_of("task.revision"), |revision_phids| async move {
+        return self
+          .search_revisions(&RevisionQuery::new().phids(revision_phids))
+          .await;
+      })
+      .await?;
+
+    let commits = self
+      .fetch_chunked(phids_of("task.commit"), |commit_phids| {
+        return self.get_commits_by_phids(commit_phids);
+      })
+      .await?;
+
+    attach_related_objects(task_family, &edges, &revisions, &commits);
+
+    return Ok(());
+  }
+
+  /// Get the task with its subtask tree, `max_depth` limits how many
+  /// subtask levels are fetched, e.g. 1 only fetches direct subtasks.
+  pub async fn get_task_family(
+    &self,
+    root_task_id: &str,
+    max_depth: Option<usize>,
+  ) -> ResultAnyError<Option<TaskFamily>> {
+    let parent_task = self.get_task_by_id(root_task_id).await?;
+
+    if parent_task.is_none() {
+      return Ok(None);
+    }
+
+    let mut task_families = self
+      .build_task_families(vec![parent_task.unwrap()], max_depth)
+      .await?;
+
+    return Ok(task_families.pop());
+  }
+
+  pub async fn get_child_tasks(
+    &self,
+    parent_task_ids: Vec<&str>,
+  ) -> ResultAnyError<Vec<TaskFamily>> {
+    if parent_task_ids.is_empty() {
+      return Err(
+        ErrorType::ValidationError {
+          message: String::from("Parent ids cannot be empty"),
         }
+        .into(),
+      );
+    }
 
-        let task_families: Vec<TaskFamily> = tasks.into_iter().map(Result::unwrap).collect();
+    let parent_tasks = self.get_tasks_by_ids(parent_task_ids).await?;
+    let task_families = self.build_task_families(parent_tasks, None).await?;
 
-        return Ok(task_families);
-      } else {
-        return Err(
-          ErrorType::ParseError {
-            message: format!("Cannot parse {}", &body),
-          }
-          .into(),
-        );
-      }
+    return Ok(
+      task_families
+        .into_iter()
+        .flat_map(|task_family| task_family.children)
+        .collect(),
+    );
+  }
+
+  /// Fetch the whole subtask tree of the given root tasks level by level,
+  /// every level takes a batched `maniphest.search` for the tasks and a batched
+  /// `edge.search` to know which parent each task belongs to. The tree is then
+  /// rebuilt locally so the number of requests grows with the tree depth
+  /// instead of the number of tasks.
+  async fn build_task_families(
+    &self,
+    root_tasks: Vec<Task>,
+    max_depth: Option<usize>,
+  ) -> ResultAnyError<Vec<TaskFamily>> {
+    let mut tree = TaskTree::new(root_tasks);
+
+    while !tree.level_ids().is_empty() && Some(tree.depth()) != max_depth {
+      let level_ids = tree.level_ids();
+
+      log::debug!("Getting child tasks of {:?}", level_ids);
+
+      let child_tasks = self
+        .fetch_chunked(level_ids, |parent_ids| async move {
+          return self
+            .search_tasks(&TaskQuery::new().parent_ids(parent_ids))
+            .await;
+        })
+        .await
+        .map_err(|err| ErrorType::FetchSubTasksError {
+          message: format!("Could not fetch sub tasks, err: {}", err),
+        })?;
+
+      let edges = self
+        .get_edges(tree.level_phids(), vec!["task.subtask"])
+        .await
+        .map_err(|err| ErrorType::FetchSubTasksError {
+          message: format!("Could not fetch sub task edges, err: {}", err),
+        })?;
+
+      tree.add_level(child_tasks, &edges);
     }
-    .boxed();
+
+    return Ok(tree.into_task_families());
+  }
+
+  /// Split the values into constraint sized chunks and fetch them
+  /// with at most `max_concurrency` requests in flight.
+  async fn fetch_chunked<'a, T, F, Fut>(
+    &self,
+    values: Vec<&'a str>,
+    fetch: F,
+  ) -> ResultAnyError<Vec<T>>
+  where
+    F: Fn(Vec<&'a str>) -> Fut,
+    Fut: Future<Output = ResultAnyError<Vec<T>>>,
+  {
+    let results: Vec<Vec<T>> = stream::iter(values.chunks(SEARCH_CONSTRAINT_CHUNK_SIZE))
+      .map(|chunk| fetch(chunk.to_vec()))
+      .buffered(self.max_concurrency)
+      .try_collect()
+      .await?;
+
+    return Ok(results.into_iter().flatten().collect());
+  }
+}
+
+/// Parse every json item, the error will list every item that could not be parsed
+/// so we know which objects are failing instead of only the first one.
+fn parse_all<T>(
+  items: &[Value],
+  parse: impl Fn(&Value) -> Result<T, ParseError>,
+) -> ResultAnyError<Vec<T>> {
+  let (parsed, failed): (Vec<_>, Vec<_>) = items.iter().map(parse).partition(Result::is_ok);
+
+  if !failed.is_empty() {
+    let message = failed
+      .into_iter()
+      .map(|result| result.err().unwrap().to_string())
+      .collect::<Vec<String>>()
+      .join("\n");
+
+    return Err(ErrorType::ParseError { message }.into());
+  }
+
+  return Ok(parsed.into_iter().map(Result::unwrap).collect());
+}
+
+/// Attach revisions and commits to every task in the family tree based on the given edges.
+fn attach_related_objects(
+  task_family: &mut TaskFamily,
+  edges: &[Edge],
+  revisions: &[Revision],
+  commits: &[Commit],
+) {
+  let task_phid = &task_family.parent_task.phid;
+  let destination_phids: Vec<&str> = edges
+    .iter()
+    .filter(|edge| &edge.source_phid == task_phid)
+    .map(|edge| edge.destination_phid.as_str())
+    .collect();
+
+  task_family.revisions = revisions
+    .iter()
+    .filter(|revision| destination_phids.contains(&revision.phid.as_str()))
+    .cloned()
+    .collect();
+
+  task_family.commits = commits
+    .iter()
+    .filter(|commit| destination_phids.contains(&commit.phid.as_str()))
+    .cloned()
+    .collect();
+
+  for child in task_family.children.iter_mut() {
+    attach_related_objects(child, edges, revisions, commits);
   }
 }
 
 #[cfg(test)]
 mod test {
+  use std::sync::Arc;
+
   use super::*;
   use crate::client::config::CertIdentityConfig;
+  use crate::client::config::RetryConfig;
+  use crate::client::transport::FakeTransport;
 
   fn dummy_config() -> PhabricatorClientConfig {
     return PhabricatorClientConfig {
       host: "http://localhost".into(),
       api_token: "foo".into(),
       cert_identity_config: None,
+      max_concurrency: 4,
+      retry_config: RetryConfig::default(),
+      connect_timeout_secs: 10,
+      request_timeout_secs: 60,
+      rate_limit_config: None,
+      cache_config: None,
     };
   }
 
@@ -387,4 +1144,352 @@ mod test {
       .to_string()
       .contains("Failed to read pkcs12 from /path/to/invalid/config"));
   }
+
+  fn task_json(id: u64) -> Value {
+    return Task::fake_json(id, &format!("Task {}", id));
+  }
+
+  /// Fake conduit with T1 -> T2 -> T3 and T1 -> T4 subtask tree.
+  fn fake_task_tree_transport() -> FakeTransport {
+    let subtasks: Vec<(u64, u64)> = vec![(1, 2), (2, 3), (1, 4)];
+    let edge_subtasks = subtasks.clone();
+
+    let ids_of = |values: &Value| -> Vec<String> {
+      return values
+        .as_array()
+        .map(|values| {
+          return values
+            .iter()
+            .map(|value| {
+              value
+                .as_str()
+                .unwrap()
+                .trim_start_matches("PHID-TASK-")
+                .to_owned()
+            })
+            .collect();
+        })
+        .unwrap_or_default();
+    };
+
+    return FakeTransport::new()
+      .with_handler("maniphest.search", move |params| {
+        let ids = ids_of(&params["constraints"]["ids"]);
+        let parent_ids = ids_of(&params["constraints"]["parentIDs"]);
+
+        let tasks: Vec<Value> = (1..=4)
+          .filter(|id| {
+            return ids.contains(&id.to_string())
+              || subtasks
+                .iter()
+                .any(|(parent, child)| child == id && parent_ids.contains(&parent.to_string()));
+          })
+          .map(task_json)
+          .collect();
+
+        return Ok(json!({ "data": tasks, "cursor": { "after": null } }));
+      })
+      .with_handler("edge.search", move |params| {
+        let source_ids = ids_of(&params["sourcePHIDs"]);
+
+        let edges: Vec<Value> = edge_subtasks
+          .iter()
+          .filter(|(parent, _)| source_ids.contains(&parent.to_string()))
+          .map(|(parent, child)| {
+            return json!({
+              "sourcePHID": format!("PHID-TASK-{}", parent),
+              "edgeType": "task.subtask",
+              "destinationPHID": format!("PHID-TASK-{}", child),
+            });
+          })
+          .collect();
+
+        return Ok(json!({ "data": edges, "cursor": { "after": null } }));
+      });
+  }
+
+  #[tokio::test]
+  async fn test_get_task_family_with_fake_transport() {
+    let transport = Arc::new(fake_task_tree_transport());
+    let phabricator = PhabricatorClient::with_transport(transport.clone());
+
+    let task_family = phabricator
+      .get_task_family("T1", None)
+      .await
+      .unwrap()
+      .unwrap();
+
+    let child_ids: Vec<&str> = task_family
+      .children
+      .iter()
+      .map(|child| child.parent_task.id.as_str())
+      .collect();
+
+    assert_eq!(child_ids, vec!["2", "4"]);
+    assert_eq!(task_family.children[0].children[0].parent_task.id, "3");
+
+    // Root task, then a task search and an edge search for each of the 3 levels
+    assert_eq!(transport.calls().len(), 7);
+  }
+
+  #[tokio::test]
+  async fn test_get_task_family_respects_max_depth() {
+    let phabricator = PhabricatorClient::with_transport(fake_task_tree_transport());
+
+    let task_family = phabricator
+      .get_task_family("T1", Some(1))
+      .await
+      .unwrap()
+      .unwrap();
+
+    assert_eq!(task_family.children.len(), 2);
+    assert!(task_family
+      .children
+      .iter()
+      .all(|child| child.children.is_empty()));
+  }
+
+  #[tokio::test]
+  async fn test_related_objects_are_attached_to_their_task() {
+    // Enough tasks for the edge search to be split into 2 chunks
+    let children: Vec<TaskFamily> = (2..=150)
+      .map(|id| TaskFamily::new(Task::fake(id, &format!("Task {}", id)), vec![]))
+      .collect();
+
+    let mut task_family = TaskFamily::new(Task::fake(1, "Task 1"), children);
+
+    let edges = [
+      ("PHID-TASK-1", "task.revision", "PHID-DREV-1"),
+      ("PHID-TASK-3", "task.revision", "PHID-DREV-2"),
+      ("PHID-TASK-3", "task.commit", "PHID-CMIT-1"),
+      ("PHID-TASK-150", "task.revision", "PHID-DREV-1"),
+    ];
+
+    let phids_of = |values: &Value| -> Vec<String> {
+      return values
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|value| value.as_str().unwrap().to_owned())
+        .collect();
+    };
+
+    let transport = Arc::new(
+      FakeTransport::new()
+        .with_handler("edge.search", move |params| {
+          let source_phids = phids_of(&params["sourcePHIDs"]);
+
+          let edges: Vec<Value> = edges
+            .iter()
+            .filter(|(source, _, _)| source_phids.contains(&source.to_string()))
+            .map(|(source, edge_type, destination)| {
+              return json!({
+                "sourcePHID": source,
+                "edgeType": edge_type,
+                "destinationPHID": destination,
+              });
+            })
+            .collect();
+
+          return Ok(json!({ "data": edges, "cursor": { "after": null } }));
+        })
+        .with_handler("differential.revision.search", move |params| {
+          let revisions: Vec<Value> = phids_of(&params["constraints"]["phids"])
+            .iter()
+            .map(|phid| {
+              return json!({
+                "id": phid.trim_start_matches("PHID-DREV-").parse::<u64>().unwrap(),
+                "phid": phid,
+                "fields": {
+                  "title": format!("Revision {}", phid),
+                  "status": { "value": "accepted", "name": "Accepted" },
+                  "authorPHID": "PHID-USER-1",
+                  "repositoryPHID": null,
+                  "diffPHID": null,
+                  "dateCreated": 1600000000,
+                  "dateModified": 1600000000,
+                },
+              });
+            })
+            .collect();
+
+          return Ok(json!({ "data": revisions, "cursor": { "after": null } }));
+        })
+        .with_result(
+          "diffusion.commit.search",
+          json!({
+            "data": [{
+              "id": 1,
+              "phid": "PHID-CMIT-1",
+              "fields": {
+                "identifier": "abc123",
+                "repositoryPHID": "PHID-REPO-1",
+                "message": "Fix login",
+                "dateCreated": 1600000000,
+                "dateModified": 1600000000,
+              },
+            }],
+            "cursor": { "after": null },
+          }),
+        ),
+    );
+
+    let phabricator = PhabricatorClient::with_transport(transport.clone());
+
+    phabricator
+      .fetch_task_family_related_objects(&mut task_family)
+      .await
+      .unwrap();
+
+    let revision_ids_of = |task_family: &TaskFamily| -> Vec<String> {
+      return task_family
+        .revisions
+        .iter()
+        .map(|revision| revision.id.clone())
+        .collect();
+    };
+
+    assert_eq!(revision_ids_of(&task_family), vec!["1"]);
+    assert!(task_family.commits.is_empty());
+
+    assert!(task_family.children[0].revisions.is_empty());
+    assert_eq!(revision_ids_of(&task_family.children[1]), vec!["2"]);
+    assert_eq!(task_family.children[1].commits[0].identifier, "abc123");
+    assert_eq!(revision_ids_of(&task_family.children[148]), vec!["1"]);
+
+    let methods: Vec<String> = transport
+      .calls()
+      .into_iter()
+      .map(|(method, _)| method)
+      .collect();
+
+    assert_eq!(
+      methods,
+      vec![
+        "edge.search",
+        "edge.search",
+        "differential.revision.search",
+        "diffusion.commit.search",
+      ]
+    );
+  }
+
+  #[tokio::test]
+  async fn test_get_tasks_by_phids_in_chunks() {
+    let transport = Arc::new(
+      FakeTransport::new().with_handler("maniphest.search", |params| {
+        let tasks: Vec<Value> = params["constraints"]["phids"]
+          .as_array()
+          .unwrap()
+          .iter()
+          .map(|phid| {
+            let id = phid.as_str().unwrap().trim_start_matches("PHID-TASK-");
+
+            return task_json(id.parse().unwrap());
+          })
+          .collect();
+
+        return Ok(json!({ "data": tasks, "cursor": { "after": null } }));
+      }),
+    );
+
+    let phabricator = PhabricatorClient::with_transport(transport.clone());
+    let task_phids: Vec<String> = (1..=150).map(|id| format!("PHID-TASK-{}", id)).collect();
+
+    let tasks = phabricator
+      .get_tasks_by_phids(task_phids.iter().map(String::as_str).collect())
+      .await
+      .unwrap();
+
+    assert_eq!(tasks.len(), 150);
+    assert_eq!(tasks[149].id, "150");
+
+    let chunk_sizes: Vec<usize> = transport
+      .calls()
+      .into_iter()
+      .map(|(_, params)| params["constraints"]["phids"].as_array().unwrap().len())
+      .collect();
+
+    assert_eq!(chunk_sizes, vec![100, 50]);
+  }
+
+  #[tokio::test]
+  async fn test_get_task_comments_oldest_first_with_authors() {
+    let comment_json = |id: u64, author: u64, content: &str| -> Value {
+      return json!({
+        "id": id,
+        "phid": format!("PHID-XACT-TASK-{}", id),
+        "type": "comment",
+        "authorPHID": format!("PHID-USER-{}", author),
+        "dateCreated": 1600000000 + id,
+        "dateModified": 1600000000 + id,
+        "comments": [{ "removed": false, "dateModified": 1600000000 + id, "content": { "raw": content } }],
+      });
+    };
+
+    let user_json = |id: u64, username: &str| -> Value {
+      return json!({
+        "id": id,
+        "phid": format!("PHID-USER-{}", id),
+        "fields": { "username": username, "realName": null, "dateCreated": 0, "dateModified": 0 },
+      });
+    };
+
+    let transport = Arc::new(
+      FakeTransport::new()
+        .with_result(
+          "transaction.search",
+          json!({
+            "data": [
+              comment_json(3, 2, "Done"),
+              { "id": 2, "phid": "PHID-XACT-TASK-2", "type": "status", "authorPHID": "PHID-USER-1" },
+              comment_json(1, 1, "Please fix"),
+            ],
+            "cursor": { "after": null },
+          }),
+        )
+        .with_result(
+          "user.search",
+          json!({
+            "data": [user_json(1, "alice"), user_json(2, "bob")],
+            "cursor": { "after": null },
+          }),
+        ),
+    );
+
+    let phabricator = PhabricatorClient::with_transport(transport.clone());
+    let comments = phabricator.get_task_comments("T9").await.unwrap();
+
+    let summaries: Vec<(&str, &str)> = comments
+      .iter()
+      .map(|comment| {
+        return (
+          comment.author.as_ref().unwrap().username.as_str(),
+          comment.content.as_str(),
+        );
+      })
+      .collect();
+
+    assert_eq!(summaries, vec![("alice", "Please fix"), ("bob", "Done")]);
+
+    let calls = transport.calls();
+
+    assert_eq!(calls[0].1["objectIdentifier"], json!("T9"));
+    assert_eq!(
+      calls[1].1["constraints"]["phids"],
+      json!(["PHID-USER-1", "PHID-USER-2"])
+    );
+  }
+
+  #[tokio::test]
+  async fn test_unknown_method_is_a_conduit_error() {
+    let phabricator = PhabricatorClient::with_transport(FakeTransport::new());
+
+    let err = phabricator.get_current_user().await.err().unwrap();
+
+    assert!(matches!(
+      err.downcast_ref::<ErrorType>(),
+      Some(ErrorType::ConduitError { code, .. }) if code == "ERR-CONDUIT-CALL"
+    ));
+  }
 }
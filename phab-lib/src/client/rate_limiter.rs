@@ -0,0 +1,139 @@
+use std::sync::Mutex as StdMutex;
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::client::config::RateLimitConfig;
+
+/// Token bucket rate limiter shared by every request of a client,
+/// concurrent requests wait for their turn in the order they ask for a token.
+pub(crate) struct RateLimiter {
+  config: Option<RateLimitConfig>,
+  bucket: Mutex<Bucket>,
+  /// Set when the server tells us to slow down, no request is sent before it.
+  paused_until: StdMutex<Option<Instant>>,
+}
+
+struct Bucket {
+  tokens: f64,
+  refilled_at: Instant,
+}
+
+impl RateLimiter {
+  /// Without config the requests are not limited, we still pause
+  /// when the server throttles us.
+  pub fn new(config: Option<RateLimitConfig>) -> RateLimiter {
+    let config = config
+      .filter(|config| config.requests_per_second > 0.0)
+      .map(|config| {
+        return RateLimitConfig {
+          // Bucket that can't hold a single token would never let a request through
+          burst: config.burst.max(1),
+          ..config
+        };
+      });
+
+    let tokens = config
+      .as_ref()
+      .map(|config| config.burst as f64)
+      .unwrap_or_default();
+
+    return RateLimiter {
+      config,
+      bucket: Mutex::new(Bucket {
+        tokens,
+        refilled_at: Instant::now(),
+      }),
+      paused_until: StdMutex::new(None),
+    };
+  }
+
+  /// Wait until a request is allowed to be sent.
+  pub async fn acquire(&self) {
+    let mut bucket = self.bucket.lock().await;
+
+    loop {
+      let paused_until = *self.paused_until.lock().unwrap();
+
+      match paused_until {
+        Some(paused_until) if paused_until > Instant::now() => {
+          tokio::time::sleep_until(paused_until).await;
+        }
+        _ => break,
+      }
+    }
+
+    let config = match &self.config {
+      Some(config) => config,
+      None => return,
+    };
+
+    bucket.refill(config);
+
+    if bucket.tokens < 1.0 {
+      let wait_secs = (1.0 - bucket.tokens) / config.requests_per_second;
+
+      tokio::time::sleep(Duration::from_secs_f64(wait_secs)).await;
+      bucket.refill(config);
+    }
+
+    bucket.tokens -= 1.0;
+  }
+
+  /// Stop sending requests for the given duration, e.g. when the server throttles us.
+  pub fn pause(&self, duration: Duration) {
+    let mut paused_until = self.paused_until.lock().unwrap();
+    let until = Instant::now() + duration;
+
+    if paused_until.map(|paused_until| paused_until < until) != Some(false) {
+      *paused_until = Some(until);
+    }
+  }
+}
+
+impl Bucket {
+  fn refill(&mut self, config: &RateLimitConfig) {
+    let now = Instant::now();
+    let elapsed_secs = now.duration_since(self.refilled_at).as_secs_f64();
+
+    self.tokens =
+      (self.tokens + elapsed_secs * config.requests_per_second).min(config.burst as f64);
+    self.refilled_at = now;
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::Duration;
+
+  use futures::future;
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_acquire_waits_for_refill_after_burst() {
+    let rate_limiter = RateLimiter::new(Some(RateLimitConfig {
+      requests_per_second: 20.0,
+      burst: 2,
+    }));
+
+    let started_at = Instant::now();
+
+    // 2 requests from the burst, the other 2 wait 50ms each
+    future::join_all((0..4).map(|_| rate_limiter.acquire())).await;
+
+    assert!(started_at.elapsed() >= Duration::from_millis(100));
+  }
+
+  #[tokio::test]
+  async fn test_pause_delays_acquire() {
+    let rate_limiter = RateLimiter::new(None);
+    let started_at = Instant::now();
+
+    rate_limiter.pause(Duration::from_millis(50));
+    rate_limiter.acquire().await;
+
+    assert!(started_at.elapsed() >= Duration::from_millis(50));
+  }
+}
@@ -0,0 +1,172 @@
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
+
+/// Conduit limits the page size to 100 items.
+const MAX_PAGE_SIZE: usize = 100;
+
+/// `differential.revision.search` query builder.
+/// ```
+/// # use serde_json::json;
+/// # use phab_lib::client::revision_query::RevisionQuery;
+///
+/// let query = RevisionQuery::new()
+///   .reviewer_phids(vec!["PHID-USER-1"])
+///   .statuses(vec!["needs-review"]);
+///
+/// let params = query.to_params();
+///
+/// assert_eq!(params["constraints"]["reviewerPHIDs"], json!(["PHID-USER-1"]));
+/// assert_eq!(params["constraints"]["statuses"], json!(["needs-review"]));
+/// assert_eq!(params["attachments"]["reviewers"], json!(true));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RevisionQuery {
+  pub query_key: Option<String>,
+  pub ids: Vec<String>,
+  pub phids: Vec<String>,
+  pub author_phids: Vec<String>,
+  pub reviewer_phids: Vec<String>,
+  pub statuses: Vec<String>,
+  pub order: Option<String>,
+  pub limit: Option<usize>,
+}
+
+fn to_strings(values: Vec<&str>) -> Vec<String> {
+  return values.into_iter().map(ToOwned::to_owned).collect();
+}
+
+impl RevisionQuery {
+  pub fn new() -> RevisionQuery {
+    return RevisionQuery::default();
+  }
+
+  /// Builtin query key e.g. `active`, `authored`, `all`
+  /// or a key of a custom saved query.
+  pub fn query_key(mut self, query_key: &str) -> RevisionQuery {
+    self.query_key = Some(query_key.to_owned());
+    return self;
+  }
+
+  pub fn ids(mut self, ids: Vec<&str>) -> RevisionQuery {
+    self.ids = to_strings(ids);
+    return self;
+  }
+
+  pub fn phids(mut self, phids: Vec<&str>) -> RevisionQuery {
+    self.phids = to_strings(phids);
+    return self;
+  }
+
+  pub fn author_phids(mut self, user_phids: Vec<&str>) -> RevisionQuery {
+    self.author_phids = to_strings(user_phids);
+    return self;
+  }
+
+  /// Reviewer user or project phids.
+  pub fn reviewer_phids(mut self, reviewer_phids: Vec<&str>) -> RevisionQuery {
+    self.reviewer_phids = to_strings(reviewer_phids);
+    return self;
+  }
+
+  /// Status keywords, e.g. `needs-review`, `needs-revision`, `accepted`, `published`.
+  pub fn statuses(mut self, statuses: Vec<&str>) -> RevisionQuery {
+    self.statuses = to_strings(statuses);
+    return self;
+  }
+
+  /// Revisions waiting for the user's review, the user and the `needs-review` status
+  /// are added to the reviewers and statuses that are already set.
+  pub fn needs_review_by(mut self, user_phid: &str) -> RevisionQuery {
+    self.reviewer_phids.push(user_phid.to_owned());
+    self.statuses.push(String::from("needs-review"));
+    return self;
+  }
+
+  /// Builtin order e.g. `updated`, `newest`, `oldest`.
+  pub fn order(mut self, order: &str) -> RevisionQuery {
+    self.order = Some(order.to_owned());
+    return self;
+  }
+
+  /// Maximum number of revisions to fetch, all matching revisions will be fetched if not set.
+  pub fn limit(mut self, limit: usize) -> RevisionQuery {
+    self.limit = Some(limit);
+    return self;
+  }
+
+  pub fn to_params(&self) -> Value {
+    let mut constraints = Map::new();
+
+    let list_constraints: Vec<(&str, Value)> = vec![
+      ("ids", json!(self.ids)),
+      ("phids", json!(self.phids)),
+      ("authorPHIDs", json!(self.author_phids)),
+      ("reviewerPHIDs", json!(self.reviewer_phids)),
+      ("statuses", json!(self.statuses)),
+    ];
+
+    for (key, value) in list_constraints {
+      if value.as_array().map(Vec::is_empty) == Some(false) {
+        constraints.insert(key.to_owned(), value);
+      }
+    }
+
+    let mut params = json!({
+      "constraints": constraints,
+      "attachments": {
+        "reviewers": true,
+      },
+    });
+
+    if let Some(query_key) = &self.query_key {
+      params["queryKey"] = json!(query_key);
+    }
+
+    if let Some(order) = &self.order {
+      params["order"] = json!(order);
+    }
+
+    if let Some(limit) = self.limit {
+      params["limit"] = json!(limit.min(MAX_PAGE_SIZE));
+    }
+
+    return params;
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_needs_review_by_keeps_other_reviewers_and_statuses() {
+    let params = RevisionQuery::new()
+      .reviewer_phids(vec!["PHID-PROJ-1"])
+      .statuses(vec!["accepted"])
+      .needs_review_by("PHID-USER-1")
+      .limit(500)
+      .to_params();
+
+    assert_eq!(
+      params["constraints"]["reviewerPHIDs"],
+      json!(["PHID-PROJ-1", "PHID-USER-1"])
+    );
+    assert_eq!(
+      params["constraints"]["statuses"],
+      json!(["accepted", "needs-review"])
+    );
+    assert_eq!(params["limit"], json!(100));
+
+    let params = RevisionQuery::new()
+      .needs_review_by("PHID-USER-1")
+      .to_params();
+
+    assert_eq!(
+      params["constraints"]["reviewerPHIDs"],
+      json!(["PHID-USER-1"])
+    );
+    assert_eq!(params["constraints"]["statuses"], json!(["needs-review"]));
+    assert!(params["constraints"].get("authorPHIDs").is_none());
+  }
+}
@@ -0,0 +1,117 @@
+use serde_json::json;
+use serde_json::Value;
+
+/// A single `maniphest.edit` transaction.
+/// Values that reference other objects (owner, projects, parents, subtasks)
+/// must be PHIDs, that's what conduit expects.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TaskTransaction {
+  Title(String),
+  Description(String),
+  /// Status keyword, e.g. `open`, `resolved`, `wontfix`.
+  Status(String),
+  /// Priority keyword, e.g. `unbreak`, `high`, `normal`, `low`, `wish`.
+  Priority(String),
+  /// `None` will unassign the task.
+  Owner(Option<String>),
+  /// `None` will clear the task points.
+  Points(Option<f64>),
+  AddProjects(Vec<String>),
+  RemoveProjects(Vec<String>),
+  SetProjects(Vec<String>),
+  /// Only valid when creating a task.
+  Parent(String),
+  AddParents(Vec<String>),
+  RemoveParents(Vec<String>),
+  AddSubtasks(Vec<String>),
+  RemoveSubtasks(Vec<String>),
+  /// Workboard column PHID, the task is moved within the column's project board.
+  Column(String),
+  Comment(String),
+}
+
+impl TaskTransaction {
+  /// Conduit transaction type of this transaction.
+  /// ```
+  /// # use phab_lib::client::task_edit::TaskTransaction;
+  ///
+  /// assert_eq!(TaskTransaction::Title("foo".into()).transaction_type(), "title");
+  /// assert_eq!(TaskTransaction::AddProjects(vec![]).transaction_type(), "projects.add");
+  /// ```
+  pub fn transaction_type(&self) -> &'static str {
+    return match self {
+      TaskTransaction::Title(_) => "title",
+      TaskTransaction::Description(_) => "description",
+      TaskTransaction::Status(_) => "status",
+      TaskTransaction::Priority(_) => "priority",
+      TaskTransaction::Owner(_) => "owner",
+      TaskTransaction::Points(_) => "points",
+      TaskTransaction::AddProjects(_) => "projects.add",
+      TaskTransaction::RemoveProjects(_) => "projects.remove",
+      TaskTransaction::SetProjects(_) => "projects.set",
+      TaskTransaction::Parent(_) => "parent",
+      TaskTransaction::AddParents(_) => "parents.add",
+      TaskTransaction::RemoveParents(_) => "parents.remove",
+      TaskTransaction::AddSubtasks(_) => "subtasks.add",
+      TaskTransaction::RemoveSubtasks(_) => "subtasks.remove",
+      TaskTransaction::Column(_) => "column",
+      TaskTransaction::Comment(_) => "comment",
+    };
+  }
+
+  pub fn value(&self) -> Value {
+    return match self {
+      TaskTransaction::Title(value)
+      | TaskTransaction::Description(value)
+      | TaskTransaction::Status(value)
+      | TaskTransaction::Priority(value)
+      | TaskTransaction::Parent(value)
+      | TaskTransaction::Comment(value) => json!(value),
+      TaskTransaction::Column(phid) => json!([phid]),
+      TaskTransaction::Owner(value) => json!(value),
+      TaskTransaction::Points(value) => json!(value),
+      TaskTransaction::AddProjects(phids)
+      | TaskTransaction::RemoveProjects(phids)
+      | TaskTransaction::SetProjects(phids)
+      | TaskTransaction::AddParents(phids)
+      | TaskTransaction::RemoveParents(phids)
+      | TaskTransaction::AddSubtasks(phids)
+      | TaskTransaction::RemoveSubtasks(phids) => json!(phids),
+    };
+  }
+
+  pub fn to_json(&self) -> Value {
+    return json!({
+      "type": self.transaction_type(),
+      "value": self.value(),
+    });
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_to_json() {
+    assert_eq!(
+      TaskTransaction::Owner(None).to_json(),
+      json!({ "type": "owner", "value": null })
+    );
+
+    assert_eq!(
+      TaskTransaction::Points(Some(2.5)).to_json(),
+      json!({ "type": "points", "value": 2.5 })
+    );
+
+    assert_eq!(
+      TaskTransaction::AddParents(vec!["PHID-TASK-1".into()]).to_json(),
+      json!({ "type": "parents.add", "value": ["PHID-TASK-1"] })
+    );
+
+    assert_eq!(
+      TaskTransaction::Column("PHID-PCOL-1".into()).to_json(),
+      json!({ "type": "column", "value": ["PHID-PCOL-1"] })
+    );
+  }
+}
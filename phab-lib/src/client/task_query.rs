@@ -0,0 +1,223 @@
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
+
+/// Conduit limits the page size to 100 items.
+const MAX_PAGE_SIZE: usize = 100;
+
+/// `maniphest.search` query builder.
+/// ```
+/// # use serde_json::json;
+/// # use phab_lib::client::task_query::TaskQuery;
+///
+/// let query = TaskQuery::new()
+///   .query_key("assigned")
+///   .statuses(vec!["open"])
+///   .modified_start(1600000000)
+///   .limit(10);
+///
+/// let params = query.to_params();
+///
+/// assert_eq!(params["queryKey"], json!("assigned"));
+/// assert_eq!(params["constraints"]["statuses"], json!(["open"]));
+/// assert_eq!(params["constraints"]["modifiedStart"], json!(1600000000));
+/// assert_eq!(params["limit"], json!(10));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TaskQuery {
+  pub query_key: Option<String>,
+  pub ids: Vec<String>,
+  pub phids: Vec<String>,
+  pub parent_ids: Vec<String>,
+  pub assigned: Vec<String>,
+  pub author_phids: Vec<String>,
+  pub statuses: Vec<String>,
+  pub priorities: Vec<u64>,
+  pub projects: Vec<String>,
+  pub subscribers: Vec<String>,
+  pub column_phids: Vec<String>,
+  pub created_start: Option<u64>,
+  pub created_end: Option<u64>,
+  pub modified_start: Option<u64>,
+  pub modified_end: Option<u64>,
+  pub query: Option<String>,
+  pub order: Option<String>,
+  pub limit: Option<usize>,
+}
+
+fn to_strings(values: Vec<&str>) -> Vec<String> {
+  return values.into_iter().map(ToOwned::to_owned).collect();
+}
+
+impl TaskQuery {
+  pub fn new() -> TaskQuery {
+    return TaskQuery::default();
+  }
+
+  /// Builtin query key e.g. `assigned`, `authored`, `subscribed`, `open`, `all`
+  /// or a key of a custom saved query.
+  pub fn query_key(mut self, query_key: &str) -> TaskQuery {
+    self.query_key = Some(query_key.to_owned());
+    return self;
+  }
+
+  pub fn ids(mut self, ids: Vec<&str>) -> TaskQuery {
+    self.ids = to_strings(ids);
+    return self;
+  }
+
+  pub fn phids(mut self, phids: Vec<&str>) -> TaskQuery {
+    self.phids = to_strings(phids);
+    return self;
+  }
+
+  pub fn parent_ids(mut self, parent_ids: Vec<&str>) -> TaskQuery {
+    self.parent_ids = to_strings(parent_ids);
+    return self;
+  }
+
+  /// Owner user phids.
+  pub fn assigned(mut self, user_phids: Vec<&str>) -> TaskQuery {
+    self.assigned = to_strings(user_phids);
+    return self;
+  }
+
+  pub fn author_phids(mut self, user_phids: Vec<&str>) -> TaskQuery {
+    self.author_phids = to_strings(user_phids);
+    return self;
+  }
+
+  /// Status keywords, e.g. `open`, `resolved`.
+  pub fn statuses(mut self, statuses: Vec<&str>) -> TaskQuery {
+    self.statuses = to_strings(statuses);
+    return self;
+  }
+
+  /// Priority values, e.g. 100 for unbreak now, 90 for needs triage.
+  pub fn priorities(mut self, priorities: Vec<u64>) -> TaskQuery {
+    self.priorities = priorities;
+    return self;
+  }
+
+  /// Project phids.
+  pub fn projects(mut self, project_phids: Vec<&str>) -> TaskQuery {
+    self.projects = to_strings(project_phids);
+    return self;
+  }
+
+  /// Subscriber user or project phids.
+  pub fn subscribers(mut self, subscriber_phids: Vec<&str>) -> TaskQuery {
+    self.subscribers = to_strings(subscriber_phids);
+    return self;
+  }
+
+  /// Workboard column phids.
+  pub fn column_phids(mut self, column_phids: Vec<&str>) -> TaskQuery {
+    self.column_phids = to_strings(column_phids);
+    return self;
+  }
+
+  /// Epoch timestamp in seconds.
+  pub fn created_start(mut self, created_start: u64) -> TaskQuery {
+    self.created_start = Some(created_start);
+    return self;
+  }
+
+  /// Epoch timestamp in seconds.
+  pub fn created_end(mut self, created_end: u64) -> TaskQuery {
+    self.created_end = Some(created_end);
+    return self;
+  }
+
+  /// Epoch timestamp in seconds.
+  pub fn modified_start(mut self, modified_start: u64) -> TaskQuery {
+    self.modified_start = Some(modified_start);
+    return self;
+  }
+
+  /// Epoch timestamp in seconds.
+  pub fn modified_end(mut self, modified_end: u64) -> TaskQuery {
+    self.modified_end = Some(modified_end);
+    return self;
+  }
+
+  /// Full-text search query.
+  pub fn query(mut self, query: &str) -> TaskQuery {
+    self.query = Some(query.to_owned());
+    return self;
+  }
+
+  /// Builtin order e.g. `priority`, `updated`, `newest`, `oldest`.
+  pub fn order(mut self, order: &str) -> TaskQuery {
+    self.order = Some(order.to_owned());
+    return self;
+  }
+
+  /// Maximum number of tasks to fetch, all matching tasks will be fetched if not set.
+  pub fn limit(mut self, limit: usize) -> TaskQuery {
+    self.limit = Some(limit);
+    return self;
+  }
+
+  pub fn to_params(&self) -> Value {
+    let mut constraints = Map::new();
+
+    let list_constraints: Vec<(&str, Value)> = vec![
+      ("ids", json!(self.ids)),
+      ("phids", json!(self.phids)),
+      ("parentIDs", json!(self.parent_ids)),
+      ("assigned", json!(self.assigned)),
+      ("authorPHIDs", json!(self.author_phids)),
+      ("statuses", json!(self.statuses)),
+      ("priorities", json!(self.priorities)),
+      ("projects", json!(self.projects)),
+      ("subscribers", json!(self.subscribers)),
+      ("columnPHIDs", json!(self.column_phids)),
+    ];
+
+    for (key, value) in list_constraints {
+      if value.as_array().map(Vec::is_empty) == Some(false) {
+        constraints.insert(key.to_owned(), value);
+      }
+    }
+
+    let optional_constraints: Vec<(&str, Value)> = vec![
+      ("createdStart", json!(self.created_start)),
+      ("createdEnd", json!(self.created_end)),
+      ("modifiedStart", json!(self.modified_start)),
+      ("modifiedEnd", json!(self.modified_end)),
+      ("query", json!(self.query)),
+    ];
+
+    for (key, value) in optional_constraints {
+      if !value.is_null() {
+        constraints.insert(key.to_owned(), value);
+      }
+    }
+
+    let mut params = json!({
+      "constraints": constraints,
+      "attachments": {
+        "columns": true,
+        "projects": true,
+      },
+    });
+
+    if let Some(query_key) = &self.query_key {
+      params["queryKey"] = json!(query_key);
+    }
+
+    // Saved queries have their own order, only default to oldest for adhoc queries.
+    match (&self.order, &self.query_key) {
+      (Some(order), _) => params["order"] = json!(order),
+      (None, None) => params["order"] = json!("oldest"),
+      (None, Some(_)) => {}
+    }
+
+    if let Some(limit) = self.limit {
+      params["limit"] = json!(limit.min(MAX_PAGE_SIZE));
+    }
+
+    return params;
+  }
+}
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+
+use crate::dto::Edge;
+use crate::dto::Task;
+use crate::dto::TaskFamily;
+
+/// Task tree that is fetched level by level and rebuilt into [TaskFamily] afterwards.
+///
+/// Every task is placed only once in the tree, a task that shows up again
+/// (it has multiple parents or the parent links form a cycle) is only referenced
+/// by its id so we don't duplicate whole subtrees or traverse forever.
+pub(crate) struct TaskTree {
+  root_phids: Vec<String>,
+  level: Vec<(String, String)>,
+  depth: usize,
+  tasks_by_phid: HashMap<String, Task>,
+  child_phids_by_parent_phid: HashMap<String, Vec<String>>,
+  referenced_ids_by_parent_phid: HashMap<String, Vec<String>>,
+}
+
+impl TaskTree {
+  pub fn new(root_tasks: Vec<Task>) -> TaskTree {
+    let mut tree = TaskTree {
+      root_phids: vec![],
+      level: vec![],
+      depth: 0,
+      tasks_by_phid: HashMap::new(),
+      child_phids_by_parent_phid: HashMap::new(),
+      referenced_ids_by_parent_phid: HashMap::new(),
+    };
+
+    for task in root_tasks {
+      if tree.tasks_by_phid.contains_key(&task.phid) {
+        continue;
+      }
+
+      tree.root_phids.push(task.phid.clone());
+      tree.level.push((task.id.clone(), task.phid.clone()));
+      tree.tasks_by_phid.insert(task.phid.clone(), task);
+    }
+
+    return tree;
+  }
+
+  /// Ids of the tasks in the deepest fetched level.
+  pub fn level_ids(&self) -> Vec<&str> {
+    return self.level.iter().map(|(id, _)| id.as_str()).collect();
+  }
+
+  /// Phids of the tasks in the deepest fetched level.
+  pub fn level_phids(&self) -> Vec<&str> {
+    return self.level.iter().map(|(_, phid)| phid.as_str()).collect();
+  }
+
+  /// Number of subtask levels below the root tasks.
+  pub fn depth(&self) -> usize {
+    return self.depth;
+  }
+
+  /// Add the children of the deepest level, `edges` are `task.subtask` edges
+  /// from the deepest level tasks, they tell which parent a child belongs to.
+  pub fn add_level(&mut self, child_tasks: Vec<Task>, edges: &[Edge]) {
+    let mut next_level: Vec<(String, String)> = vec![];
+    let parent_phids_by_child_phid = self.parent_phids_by_child_phid(edges);
+
+    for child_task in child_tasks {
+      let parent_phids: Vec<String> = parent_phids_by_child_phid
+        .get(child_task.phid.as_str())
+        .cloned()
+        .unwrap_or_default();
+
+      if parent_phids.is_empty() {
+        log::debug!("Could not find parent of task {}", child_task.id);
+        continue;
+      }
+
+      let mut referencing_parent_phids = parent_phids.as_slice();
+
+      if !self.tasks_by_phid.contains_key(&child_task.phid) {
+        // A task can have multiple parents, it's placed under the first one
+        // and only referenced by the rest.
+        self
+          .child_phids_by_parent_phid
+          .entry(parent_phids[0].clone())
+          .or_default()
+          .push(child_task.phid.clone());
+
+        referencing_parent_phids = &parent_phids[1..];
+      }
+
+      for parent_phid in referencing_parent_phids {
+        self.add_reference(parent_phid, &child_task);
+      }
+
+      if !self.tasks_by_phid.contains_key(&child_task.phid) {
+        next_level.push((child_task.id.clone(), child_task.phid.clone()));
+        self
+          .tasks_by_phid
+          .insert(child_task.phid.clone(), child_task);
+      }
+    }
+
+    self.level = next_level;
+    self.depth += 1;
+  }
+
+  /// Parents of every child in the deepest level, parents are ordered like the level.
+  fn parent_phids_by_child_phid<'a>(&self, edges: &'a [Edge]) -> HashMap<&'a str, Vec<String>> {
+    let level_index_by_phid: HashMap<&str, usize> = self
+      .level
+      .iter()
+      .enumerate()
+      .map(|(i, (_, phid))| (phid.as_str(), i))
+      .collect();
+
+    let mut parents_by_child_phid: HashMap<&str, Vec<(usize, &str)>> = HashMap::new();
+
+    for edge in edges {
+      if let Some(i) = level_index_by_phid.get(edge.source_phid.as_str()) {
+        parents_by_child_phid
+          .entry(edge.destination_phid.as_str())
+          .or_default()
+          .push((*i, edge.source_phid.as_str()));
+      }
+    }
+
+    return parents_by_child_phid
+      .into_iter()
+      .map(|(child_phid, mut parents)| {
+        parents.sort_unstable();
+        parents.dedup();
+
+        let parent_phids = parents
+          .into_iter()
+          .map(|(_, parent_phid)| parent_phid.to_owned())
+          .collect();
+
+        return (child_phid, parent_phids);
+      })
+      .collect();
+  }
+
+  fn add_reference(&mut self, parent_phid: &str, child_task: &Task) {
+    let is_placed_child = self
+      .child_phids_by_parent_phid
+      .get(parent_phid)
+      .map(|child_phids| child_phids.contains(&child_task.phid))
+      .unwrap_or(false);
+
+    let referenced_ids = self
+      .referenced_ids_by_parent_phid
+      .entry(parent_phid.to_owned())
+      .or_default();
+
+    if !is_placed_child && !referenced_ids.contains(&child_task.id) {
+      referenced_ids.push(child_task.id.clone());
+    }
+  }
+
+  pub fn into_task_families(mut self) -> Vec<TaskFamily> {
+    let root_phids = std::mem::take(&mut self.root_phids);
+
+    return root_phids
+      .iter()
+      .filter_map(|phid| self.build_task_family(phid))
+      .collect();
+  }
+
+  fn build_task_family(&mut self, task_phid: &str) -> Option<TaskFamily> {
+    let task = self.tasks_by_phid.remove(task_phid)?;
+    let child_phids = self
+      .child_phids_by_parent_phid
+      .remove(task_phid)
+      .unwrap_or_default();
+
+    let children = child_phids
+      .iter()
+      .filter_map(|child_phid| self.build_task_family(child_phid))
+      .collect();
+
+    let mut task_family = TaskFamily::new(task, children);
+
+    task_family.referenced_children = self
+      .referenced_ids_by_parent_phid
+      .remove(task_phid)
+      .unwrap_or_default();
+
+    return Some(task_family);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use fake::Fake;
+  use fake::Faker;
+
+  use super::*;
+
+  fn task(id: &str) -> Task {
+    let mut task: Task = Faker.fake();
+    task.id = id.to_owned();
+    task.phid = format!("PHID-TASK-{}", id);
+
+    return task;
+  }
+
+  fn subtask_edge(parent_id: &str, child_id: &str) -> Edge {
+    return Edge {
+      source_phid: format!("PHID-TASK-{}", parent_id),
+      edge_type: String::from("task.subtask"),
+      destination_phid: format!("PHID-TASK-{}", child_id),
+    };
+  }
+
+  #[test]
+  fn test_shared_child_and_cycle_are_only_referenced() {
+    let mut tree = TaskTree::new(vec![task("1")]);
+
+    tree.add_level(
+      vec![task("2"), task("3")],
+      &[subtask_edge("1", "2"), subtask_edge("1", "3")],
+    );
+
+    assert_eq!(tree.level_ids(), vec!["2", "3"]);
+
+    // 4 is a subtask of both 2 and 3, 3 links back to the root
+    tree.add_level(
+      vec![task("4"), task("1")],
+      &[
+        subtask_edge("2", "4"),
+        subtask_edge("3", "4"),
+        subtask_edge("3", "1"),
+      ],
+    );
+
+    assert_eq!(tree.level_ids(), vec!["4"]);
+
+    tree.add_level(vec![], &[]);
+
+    assert!(tree.level_ids().is_empty());
+    assert_eq!(tree.depth(), 3);
+
+    let task_families = tree.into_task_families();
+
+    assert_eq!(task_families.len(), 1);
+
+    let root = &task_families[0];
+    let child_ids: Vec<&str> = root
+      .children
+      .iter()
+      .map(|child| child.parent_task.id.as_str())
+      .collect();
+
+    assert_eq!(child_ids, vec!["2", "3"]);
+    assert_eq!(root.children[0].children[0].parent_task.id, "4");
+    assert!(root.children[1].children.is_empty());
+    assert_eq!(root.children[1].referenced_children, vec!["4", "1"]);
+    assert_eq!(root.tasks().len(), 4);
+  }
+}
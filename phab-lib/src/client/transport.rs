@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::Error;
+use futures::future::BoxFuture;
+use futures::future::FutureExt;
+use reqwest::header::RETRY_AFTER;
+use reqwest::Client as HttpClient;
+use reqwest::ClientBuilder as HttpClientBuilder;
+use reqwest::Identity;
+use reqwest::StatusCode;
+use serde_json::Value;
+
+use crate::client::config::PhabricatorClientConfig;
+use crate::client::config::RetryConfig;
+use crate::client::phabricator::form_fields_from_json;
+use crate::client::phabricator::ErrorType;
+use crate::client::rate_limiter::RateLimiter;
+use crate::types::ResultAnyError;
+
+/// Sends conduit calls, [PhabricatorClient](crate::client::phabricator::PhabricatorClient)
+/// builds the params and parses the result on top of it.
+pub trait ConduitTransport: Send + Sync {
+  /// Call conduit `method` and return the `result` part of the response body,
+  /// conduit errors are returned as [ErrorType::ConduitError].
+  fn call<'a>(&'a self, method: &'a str, params: &'a Value)
+    -> BoxFuture<'a, ResultAnyError<Value>>;
+}
+
+impl<T: ConduitTransport + ?Sized> ConduitTransport for Arc<T> {
+  fn call<'a>(
+    &'a self,
+    method: &'a str,
+    params: &'a Value,
+  ) -> BoxFuture<'a, ResultAnyError<Value>> {
+    return (**self).call(method, params);
+  }
+}
+
+/// Conduit over http, every call is a form post to `{host}/api/{method}`.
+pub struct HttpTransport {
+  http: HttpClient,
+  host: String,
+  api_token: String,
+  retry_config: RetryConfig,
+  rate_limiter: RateLimiter,
+}
+
+/// Response of a single conduit http request.
+struct HttpResponse {
+  status: StatusCode,
+  retry_after: Option<Duration>,
+  text: String,
+}
+
+impl HttpTransport {
+  pub fn new(config: PhabricatorClientConfig) -> ResultAnyError<HttpTransport> {
+    let PhabricatorClientConfig {
+      host,
+      api_token,
+      cert_identity_config,
+      retry_config,
+      connect_timeout_secs,
+      request_timeout_secs,
+      rate_limit_config,
+      ..
+    } = config;
+
+    let mut http_client_builder = Ok(
+      HttpClientBuilder::new()
+        .connect_timeout(Duration::from_secs(connect_timeout_secs))
+        .timeout(Duration::from_secs(request_timeout_secs)),
+    );
+
+    let cert_identity: Option<Result<_, _>> = cert_identity_config.map(|config| {
+      return fs::read(&config.pkcs12_path)
+        .map_err(|err| ErrorType::FailToConfigureHttpClient {
+          message: format!("Failed to read pkcs12 from {}, {}", config.pkcs12_path, err),
+        })
+        .and_then(|bytes| {
+          return Identity::from_pkcs12_der(&bytes, &config.pkcs12_password).map_err(|err| {
+            ErrorType::CertificateIdentityError {
+              pkcs12_path: config.pkcs12_path,
+              message: err.to_string(),
+            }
+          });
+        });
+    });
+
+    if let Some(cert_identity) = cert_identity {
+      http_client_builder =
+        http_client_builder.and_then(|http_client_builder: HttpClientBuilder| {
+          return cert_identity.map(|cert_identity: Identity| {
+            return http_client_builder.identity(cert_identity);
+          });
+        });
+    }
+
+    return http_client_builder
+      .and_then(|http_client_builder| {
+        http_client_builder
+          .build()
+          .map_err(|err| ErrorType::FailToConfigureHttpClient {
+            message: err.to_string(),
+          })
+      })
+      .map_err(Error::new)
+      .map(|http_client| {
+        return HttpTransport {
+          http: http_client,
+          host,
+          api_token,
+          retry_config,
+          rate_limiter: RateLimiter::new(rate_limit_config),
+        };
+      });
+  }
+
+  async fn call_conduit(&self, method: &str, params: &Value) -> ResultAnyError<Value> {
+    let mut form: Vec<(String, String)> = vec![("api.token".to_owned(), self.api_token.clone())];
+
+    form.extend(form_fields_from_json("", params));
+
+    let url = format!("{}/api/{}", self.host, method);
+
+    log::debug!("Calling {} {:?}", url, form);
+
+    let (status, response_text) = self.post_with_retry(method, &url, &form).await?;
+
+    log::debug!("Response {}", response_text);
+
+    let body: Value = serde_json::from_str(response_text.as_str()).map_err(|err| {
+      return ErrorType::ParseError {
+        message: format!(
+          "Cannot parse {} response with http status {}, {}, response: {}",
+          method,
+          status,
+          err,
+          response_text.chars().take(200).collect::<String>()
+        ),
+      };
+    })?;
+
+    return result_from_body(method, body);
+  }
+
+  /// Post the conduit form, transient failures are retried based on `retry_config`.
+  async fn post_with_retry(
+    &self,
+    method: &str,
+    url: &str,
+    form: &[(String, String)],
+  ) -> ResultAnyError<(StatusCode, String)> {
+    let retry_config = &self.retry_config;
+    let is_read_method = is_read_method(method);
+    let mut attempt = 1;
+
+    loop {
+      self.rate_limiter.acquire().await;
+
+      let result = self.post(url, form).await;
+      let is_throttled = matches!(&result, Ok(response) if HttpTransport::is_throttled(response));
+
+      let should_retry = attempt < retry_config.max_attempts
+        && match &result {
+          // Throttled requests are rejected before being processed
+          Ok(_) if is_throttled => true,
+          Ok(response) => {
+            is_read_method
+              && retry_config
+                .retry_on_statuses
+                .contains(&response.status.as_u16())
+          }
+          // Connect failure means the request never reached the server
+          Err(err) => err.is_connect() || (is_read_method && err.is_timeout()),
+        };
+
+      if !should_retry {
+        let response = result.map_err(Error::new)?;
+
+        return Ok((response.status, response.text));
+      }
+
+      let backoff = match &result {
+        Ok(response) => response
+          .retry_after
+          .map(|retry_after| retry_config.retry_after(retry_after)),
+        Err(_) => None,
+      }
+      .unwrap_or_else(|| retry_config.backoff(attempt));
+
+      log::debug!(
+        "Retrying {} in {:?}, attempt {} failed with {:?}",
+        method,
+        backoff,
+        attempt,
+        result.map(|response| response.status)
+      );
+
+      if is_throttled {
+        // Every request of this client will wait, not only this one
+        self.rate_limiter.pause(backoff);
+      } else {
+        tokio::time::sleep(backoff).await;
+      }
+
+      attempt += 1;
+    }
+  }
+
+  async fn post(
+    &self,
+    url: &str,
+    form: &[(String, String)],
+  ) -> Result<HttpResponse, reqwest::Error> {
+    let response = self.http.post(url).form(form).send().await?;
+    let status = response.status();
+    let retry_after = response
+      .headers()
+      .get(RETRY_AFTER)
+      .and_then(|retry_after| retry_after.to_str().ok())
+      .and_then(|retry_after| retry_after.trim().parse().ok())
+      .map(Duration::from_secs);
+
+    let text = response.text().await?;
+
+    return Ok(HttpResponse {
+      status,
+      retry_after,
+      text,
+    });
+  }
+
+  /// Server throttles with http 429 or, for phabricator rate limits,
+  /// a conduit error telling us that we're doing too many actions.
+  fn is_throttled(response: &HttpResponse) -> bool {
+    if response.status == StatusCode::TOO_MANY_REQUESTS {
+      return true;
+    }
+
+    if !response.text.contains("error_code") {
+      return false;
+    }
+
+    let body: Value = serde_json::from_str(&response.text).unwrap_or_default();
+    let code = body["error_code"].as_str().unwrap_or_default();
+    let info = body["error_info"]
+      .as_str()
+      .unwrap_or_default()
+      .to_lowercase();
+
+    return code == "ERR-RATE-LIMIT" || info.contains("rate limit") || info.contains("too many");
+  }
+}
+
+impl ConduitTransport for HttpTransport {
+  fn call<'a>(
+    &'a self,
+    method: &'a str,
+    params: &'a Value,
+  ) -> BoxFuture<'a, ResultAnyError<Value>> {
+    return self.call_conduit(method, params).boxed();
+  }
+}
+
+/// Read methods can be retried or cached safely, edits might have been applied already.
+pub(crate) fn is_read_method(method: &str) -> bool {
+  return method.ends_with(".search") || method.ends_with(".query") || method == "user.whoami";
+}
+
+/// Take the `result` out of a conduit response body, the body is checked
+/// for `error_code` first because conduit responds with http 200 even for failures.
+pub fn result_from_body(method: &str, mut body: Value) -> ResultAnyError<Value> {
+  if let Some(code) = body["error_code"].as_str() {
+    return Err(
+      ErrorType::ConduitError {
+        code: code.to_owned(),
+        info: body["error_info"].as_str().unwrap_or_default().to_owned(),
+        method: method.to_owned(),
+      }
+      .into(),
+    );
+  }
+
+  if body["result"].is_null() {
+    return Err(
+      ErrorType::ParseError {
+        message: format!("Cannot parse {} response {}", method, &body),
+      }
+      .into(),
+    );
+  }
+
+  return Ok(body["result"].take());
+}
+
+type FakeHandler = Box<dyn Fn(&Value) -> ResultAnyError<Value> + Send + Sync>;
+
+/// In-memory transport that answers conduit calls with registered handlers,
+/// every call is recorded so it can be asserted afterwards.
+/// ```
+/// # use serde_json::json;
+/// # use phab_lib::client::phabricator::PhabricatorClient;
+/// # use phab_lib::client::transport::FakeTransport;
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let transport = FakeTransport::new().with_result(
+///   "user.search",
+///   json!({ "data": [], "cursor": { "after": null } }),
+/// );
+///
+/// let phabricator = PhabricatorClient::with_transport(transport);
+/// let users = phabricator.get_users_by_usernames(vec!["foo"]).await.unwrap();
+///
+/// assert!(users.is_empty());
+/// # });
+/// ```
+#[derive(Default)]
+pub struct FakeTransport {
+  handlers: HashMap<String, FakeHandler>,
+  calls: Mutex<Vec<(String, Value)>>,
+}
+
+impl FakeTransport {
+  pub fn new() -> FakeTransport {
+    return FakeTransport::default();
+  }
+
+  /// Answer `method` calls with the `result` returned by the handler.
+  pub fn with_handler(
+    mut self,
+    method: &str,
+    handler: impl Fn(&Value) -> ResultAnyError<Value> + Send + Sync + 'static,
+  ) -> FakeTransport {
+    self.handlers.insert(method.to_owned(), Box::new(handler));
+    return self;
+  }
+
+  /// Answer every `method` call with the same `result`.
+  pub fn with_result(self, method: &str, result: Value) -> FakeTransport {
+    return self.with_handler(method, move |_| Ok(result.clone()));
+  }
+
+  /// Calls that were made so far as (method, params), oldest first.
+  pub fn calls(&self) -> Vec<(String, Value)> {
+    return self.calls.lock().unwrap().clone();
+  }
+}
+
+impl ConduitTransport for FakeTransport {
+  fn call<'a>(
+    &'a self,
+    method: &'a str,
+    params: &'a Value,
+  ) -> BoxFuture<'a, ResultAnyError<Value>> {
+    self
+      .calls
+      .lock()
+      .unwrap()
+      .push((method.to_owned(), params.clone()));
+
+    let result = match self.handlers.get(method) {
+      Some(handler) => handler(params),
+      // Same error that conduit gives for unknown methods
+      None => Err(
+        ErrorType::ConduitError {
+          code: String::from("ERR-CONDUIT-CALL"),
+          info: format!("Conduit method '{}' does not exist.", method),
+          method: method.to_owned(),
+        }
+        .into(),
+      ),
+    };
+
+    return futures::future::ready(result).boxed();
+  }
+}
@@ -11,12 +11,54 @@ use crate::types::ResultAnyError;
 pub struct TaskFamily {
   pub parent_task: Task,
   pub children: Vec<TaskFamily>,
+  /// Ids of subtasks that are already listed elsewhere in the tree, either
+  /// shared with another parent or linking back to an ancestor.
+  #[serde(default)]
+  pub referenced_children: Vec<String>,
+  /// Revisions attached to the parent task.
+  #[serde(default)]
+  pub revisions: Vec<Revision>,
+  /// Commits attached to the parent task.
+  #[serde(default)]
+  pub commits: Vec<Commit>,
 }
 
 impl TaskFamily {
+  pub fn new(parent_task: Task, children: Vec<TaskFamily>) -> TaskFamily {
+    return TaskFamily {
+      parent_task,
+      children,
+      referenced_children: vec![],
+      revisions: vec![],
+      commits: vec![],
+    };
+  }
+
   pub fn json_string(task_families: &[TaskFamily]) -> ResultAnyError<String> {
     return serde_json::to_string(task_families).map_err(Error::new);
   }
+
+  /// All tasks in the family tree, parent first.
+  pub fn tasks(&self) -> Vec<&Task> {
+    let mut tasks = vec![&self.parent_task];
+
+    for child in self.children.iter() {
+      tasks.extend(child.tasks());
+    }
+
+    return tasks;
+  }
+
+  /// Same as `tasks` but mutable.
+  pub fn tasks_mut(&mut self) -> Vec<&mut Task> {
+    let mut tasks = vec![&mut self.parent_task];
+
+    for child in self.children.iter_mut() {
+      tasks.extend(child.tasks_mut());
+    }
+
+    return tasks;
+  }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Dummy)]
@@ -28,79 +70,254 @@ pub struct Task {
   pub description: String,
   pub author_phid: String,
   pub assigned_phid: Option<String>,
+  /// Resolved from `author_phid`, only set when the task users are fetched.
+  #[serde(default)]
+  pub author: Option<User>,
+  /// Resolved from `assigned_phid`, only set when the task users are fetched.
+  #[serde(default)]
+  pub assigned: Option<User>,
   pub status: String,
   pub priority: String,
-  pub point: Option<u64>,
+  pub point: Option<f64>,
   pub project_phids: Vec<String>,
-  pub board: Option<Board>,
+  /// Resolved from `project_phids`, only set when the task projects are fetched.
+  #[serde(default)]
+  pub projects: Vec<Project>,
+  /// Column of every workboard the task is on.
+  #[serde(default)]
+  pub boards: Vec<Board>,
   pub created_at: u64,
   pub updated_at: u64,
 }
 
 impl Task {
-  pub fn from_json(v: &Value) -> Task {
-    let project_phids: &Vec<Value> = match &v["attachments"]["projects"]["projectPHIDs"] {
-      Value::Array(arr) => arr,
-      _ => panic!(
-        "Project phids is not an array {:?}",
-        v["attachments"]["projects"]["projectPHIDs"]
-      ),
-    };
+  pub fn from_json(v: &Value) -> Result<Task, ParseError> {
+    let parser = ObjectParser::new("task", v);
 
-    let project_phids: Vec<String> = project_phids.iter().map(json_to_string).collect();
+    let project_phids: Vec<String> = match parser.get("attachments.projects.projectPHIDs") {
+      Value::Array(phids) => phids
+        .iter()
+        .map(|phid| parser.string_of(phid, "attachments.projects.projectPHIDs[]"))
+        .collect::<Result<_, _>>()?,
+      Value::Null => vec![],
+      _ => return Err(parser.error("attachments.projects.projectPHIDs", "expected an array")),
+    };
 
-    let board: Option<&Value> =
-      Task::guess_board_from_projects(&v["attachments"]["columns"]["boards"], &project_phids);
-    let fields: &Value = &v["fields"];
+    let boards = Task::boards_from_json(&parser, &project_phids)?;
 
     let task = Task {
-      id: format!("{}", v["id"].as_u64().unwrap()),
-      task_type: json_to_string(&v["type"]),
-      phid: json_to_string(&v["phid"]),
-      name: json_to_string(&fields["name"]),
-      description: json_to_string(&fields["description"]["raw"]),
-      author_phid: json_to_string(&fields["authorPHID"]),
-      assigned_phid: fields["ownerPHID"].as_str().map(Into::into),
-      status: json_to_string(&fields["status"]["value"]),
-      priority: json_to_string(&fields["priority"]["name"]),
-      point: fields["points"].as_u64(),
+      id: parser.id()?,
+      task_type: parser.string("type")?,
+      phid: parser.string("phid")?,
+      name: parser.string("fields.name")?,
+      // Description is nullable, we treat it as empty description.
+      description: parser
+        .optional_string("fields.description.raw")?
+        .unwrap_or_default(),
+      author_phid: parser.string("fields.authorPHID")?,
+      assigned_phid: parser.optional_string("fields.ownerPHID")?,
+      author: None,
+      assigned: None,
+      status: parser.string("fields.status.value")?,
+      priority: parser.string("fields.priority.name")?,
+      point: parser.optional_points("fields.points")?,
       project_phids,
-      board: board.map(|board: &Value| {
-        return Board {
-          id: board["id"].as_u64().unwrap(),
-          phid: board["phid"].as_str().unwrap().into(),
-          name: board["name"].as_str().unwrap().into(),
-        };
-      }),
-      created_at: fields["dateCreated"].as_u64().unwrap(),
-      updated_at: fields["dateModified"].as_u64().unwrap(),
+      projects: vec![],
+      boards,
+      created_at: parser.u64("fields.dateCreated")?,
+      updated_at: parser.u64("fields.dateModified")?,
     };
 
-    return task;
+    return Ok(task);
   }
 
-  pub fn guess_board_from_projects<'a>(
-    boards: &'a Value,
+  /// Boards are keyed by project phid, conduit gives an empty list
+  /// instead of an empty object when the task is not on any board.
+  fn boards_from_json(
+    parser: &ObjectParser,
     project_phids: &[String],
-  ) -> Option<&'a Value> {
-    return project_phids
+  ) -> Result<Vec<Board>, ParseError> {
+    let boards = parser.get("attachments.columns.boards");
+    let mut task_boards = vec![];
+
+    for project_phid in project_phids {
+      let columns = match &boards[project_phid]["columns"] {
+        Value::Array(columns) => columns,
+        _ => continue,
+      };
+
+      for column in columns {
+        task_boards.push(Board {
+          id: parser.u64_of(&column["id"], "attachments.columns.boards[].columns[].id")?,
+          phid: parser.string_of(
+            &column["phid"],
+            "attachments.columns.boards[].columns[].phid",
+          )?,
+          name: parser.string_of(
+            &column["name"],
+            "attachments.columns.boards[].columns[].name",
+          )?,
+          project_phid: project_phid.clone(),
+        });
+      }
+    }
+
+    return Ok(task_boards);
+  }
+
+  /// Project of the given board, only available when the task projects are fetched.
+  pub fn board_project(&self, board: &Board) -> Option<&Project> {
+    return self
+      .projects
       .iter()
-      .find(|phid| {
-        return boards[phid] != Value::Null;
+      .find(|project| project.phid == board.project_phid);
+  }
+
+  /// Board columns of the task labeled with their project name when it's known,
+  /// e.g. `Backend: In Progress`.
+  pub fn board_labels(&self) -> Vec<String> {
+    return self
+      .boards
+      .iter()
+      .map(|board| match self.board_project(board) {
+        Some(project) => format!("{}: {}", project.name, board.name),
+        None => board.name.clone(),
       })
-      .map(|phid| &boards[&phid]["columns"][0]);
+      .collect();
+  }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl Task {
+  /// `maniphest.search` item of an open task without owner, points or projects.
+  pub fn fake_json(id: u64, name: &str) -> Value {
+    return serde_json::json!({
+      "id": id,
+      "type": "TASK",
+      "phid": format!("PHID-TASK-{}", id),
+      "fields": {
+        "name": name,
+        "description": { "raw": "" },
+        "authorPHID": "PHID-USER-1",
+        "ownerPHID": null,
+        "status": { "value": "open", "name": "Open" },
+        "priority": { "value": 90, "name": "Needs Triage" },
+        "points": null,
+        "dateCreated": 1600000000,
+        "dateModified": 1600000000,
+      },
+      "attachments": {},
+    });
+  }
+
+  /// Task parsed from `Task::fake_json`.
+  pub fn fake(id: u64, name: &str) -> Task {
+    return Task::from_json(&Task::fake_json(id, name)).unwrap();
   }
 }
 
-fn json_to_string(v: &Value) -> String {
-  return v.as_str().unwrap().into();
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("Could not parse {object_type} {object_id}, field {field}: {message}")]
+pub struct ParseError {
+  pub object_type: String,
+  pub object_id: String,
+  pub field: String,
+  pub message: String,
+}
+
+/// Read fields of a conduit object, errors will name the object and the field.
+struct ObjectParser<'a> {
+  object_type: &'static str,
+  object: &'a Value,
+}
+
+impl<'a> ObjectParser<'a> {
+  fn new(object_type: &'static str, object: &'a Value) -> ObjectParser<'a> {
+    return ObjectParser {
+      object_type,
+      object,
+    };
+  }
+
+  /// Get value by dot separated path, e.g. `fields.status.value`.
+  fn get(&self, path: &str) -> &'a Value {
+    return path
+      .split('.')
+      .fold(self.object, |value: &'a Value, key| &value[key]);
+  }
+
+  fn error(&self, field: &str, message: &str) -> ParseError {
+    let object_id = match (&self.object["id"], &self.object["phid"]) {
+      (Value::Number(id), _) => id.to_string(),
+      (_, Value::String(phid)) => phid.clone(),
+      _ => String::from("<unknown>"),
+    };
+
+    return ParseError {
+      object_type: self.object_type.to_owned(),
+      object_id,
+      field: field.to_owned(),
+      message: message.to_owned(),
+    };
+  }
+
+  fn id(&self) -> Result<String, ParseError> {
+    return self.u64("id").map(|id| format!("{}", id));
+  }
+
+  fn string(&self, path: &str) -> Result<String, ParseError> {
+    return self.string_of(self.get(path), path);
+  }
+
+  fn string_of(&self, value: &Value, field: &str) -> Result<String, ParseError> {
+    return value
+      .as_str()
+      .map(Into::into)
+      .ok_or_else(|| self.error(field, &format!("expected a string, got {}", value)));
+  }
+
+  fn optional_string(&self, path: &str) -> Result<Option<String>, ParseError> {
+    return match self.get(path) {
+      Value::Null => Ok(None),
+      value => self.string_of(value, path).map(Some),
+    };
+  }
+
+  fn u64(&self, path: &str) -> Result<u64, ParseError> {
+    return self.u64_of(self.get(path), path);
+  }
+
+  fn u64_of(&self, value: &Value, field: &str) -> Result<u64, ParseError> {
+    return value
+      .as_u64()
+      .ok_or_else(|| self.error(field, &format!("expected a number, got {}", value)));
+  }
+
+  /// Points could be null, a number or a numeric string depending on phabricator version,
+  /// they may be fractional e.g. `0.5`.
+  fn optional_points(&self, path: &str) -> Result<Option<f64>, ParseError> {
+    return match self.get(path) {
+      Value::Null => Ok(None),
+      Value::Number(points) => Ok(points.as_f64()),
+      Value::String(points) => points
+        .parse::<f64>()
+        .map(Some)
+        .map_err(|_| self.error(path, &format!("expected a number, got {}", points))),
+      value => Err(self.error(path, &format!("expected a number, got {}", value))),
+    };
+  }
 }
 
+/// Column that a task is in on a project workboard.
 #[derive(Clone, Debug, Serialize, Deserialize, Dummy)]
 pub struct Board {
+  /// Column id.
   pub id: u64,
+  /// Column phid.
   pub phid: String,
+  /// Column name.
   pub name: String,
+  pub project_phid: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Dummy)]
@@ -121,16 +338,471 @@ pub struct User {
 }
 
 impl User {
-  pub fn from_json(v: &Value) -> User {
-    let fields: &Value = &v["fields"];
-
-    return User {
-      id: format!("{}", v["id"].as_u64().unwrap()),
-      phid: json_to_string(&v["phid"]),
-      username: json_to_string(&fields["username"]),
-      name: json_to_string(&fields["realName"]),
-      created_at: fields["dateCreated"].as_u64().unwrap(),
-      updated_at: fields["dateModified"].as_u64().unwrap(),
+  /// Username as it's mentioned in phabricator, e.g. `@alice`.
+  pub fn mention(&self) -> String {
+    return format!("@{}", self.username);
+  }
+
+  pub fn from_json(v: &Value) -> Result<User, ParseError> {
+    let parser = ObjectParser::new("user", v);
+
+    return Ok(User {
+      id: parser.id()?,
+      phid: parser.string("phid")?,
+      username: parser.string("fields.username")?,
+      name: parser
+        .optional_string("fields.realName")?
+        .unwrap_or_default(),
+      created_at: parser.u64("fields.dateCreated")?,
+      updated_at: parser.u64("fields.dateModified")?,
+    });
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Dummy)]
+pub struct Project {
+  pub id: String,
+  pub phid: String,
+  pub name: String,
+  pub slug: Option<String>,
+  pub created_at: u64,
+  pub updated_at: u64,
+}
+
+impl Project {
+  pub fn from_json(v: &Value) -> Result<Project, ParseError> {
+    let parser = ObjectParser::new("project", v);
+
+    return Ok(Project {
+      id: parser.id()?,
+      phid: parser.string("phid")?,
+      name: parser.string("fields.name")?,
+      slug: parser.optional_string("fields.slug")?,
+      created_at: parser.u64("fields.dateCreated")?,
+      updated_at: parser.u64("fields.dateModified")?,
+    });
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Dummy)]
+pub struct Column {
+  pub id: String,
+  pub phid: String,
+  pub name: String,
+  pub project_phid: String,
+  /// Hidden columns are not shown on the workboard.
+  pub is_hidden: bool,
+  pub created_at: u64,
+  pub updated_at: u64,
+}
+
+impl Column {
+  pub fn from_json(v: &Value) -> Result<Column, ParseError> {
+    let parser = ObjectParser::new("column", v);
+
+    return Ok(Column {
+      id: parser.id()?,
+      phid: parser.string("phid")?,
+      name: parser.string("fields.name")?,
+      project_phid: parser.string("fields.project.phid")?,
+      is_hidden: parser.get("fields.isHidden").as_bool().unwrap_or(false),
+      created_at: parser.u64("fields.dateCreated")?,
+      updated_at: parser.u64("fields.dateModified")?,
+    });
+  }
+}
+
+/// Project workboard, every lane is a column with the tasks in it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Workboard {
+  pub project: Project,
+  pub lanes: Vec<WorkboardLane>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorkboardLane {
+  pub column: Column,
+  pub tasks: Vec<Task>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Dummy)]
+pub struct Comment {
+  pub id: String,
+  pub phid: String,
+  pub author_phid: String,
+  pub author: Option<User>,
+  pub content: String,
+  pub created_at: u64,
+  pub updated_at: u64,
+}
+
+impl Comment {
+  /// Parse comment from a `transaction.search` transaction,
+  /// returns `None` if the transaction is not a comment or the comment was removed.
+  pub fn from_transaction_json(v: &Value) -> Result<Option<Comment>, ParseError> {
+    if v["type"].as_str() != Some("comment") {
+      return Ok(None);
+    }
+
+    let parser = ObjectParser::new("transaction", v);
+
+    // Conduit returns every version of the comment, latest version first.
+    let comment: &Value = match v["comments"].as_array().and_then(|c| c.first()) {
+      Some(comment) => comment,
+      None => return Ok(None),
+    };
+
+    if comment["removed"].as_bool() == Some(true) {
+      return Ok(None);
+    }
+
+    return Ok(Some(Comment {
+      id: parser.id()?,
+      phid: parser.string("phid")?,
+      author_phid: parser.string("authorPHID")?,
+      author: None,
+      content: parser.string_of(&comment["content"]["raw"], "comments[].content.raw")?,
+      created_at: parser.u64("dateCreated")?,
+      updated_at: parser.u64_of(&comment["dateModified"], "comments[].dateModified")?,
+    }));
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Dummy)]
+pub struct Reviewer {
+  /// Could be a user, project or package phid.
+  pub reviewer_phid: String,
+  /// Review status e.g. `added`, `accepted`, `rejected`, `blocking`.
+  pub status: String,
+  pub is_blocking: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Dummy)]
+pub struct Revision {
+  pub id: String,
+  pub phid: String,
+  pub title: String,
+  /// Status keyword e.g. `needs-review`, `accepted`, `published`.
+  pub status: String,
+  /// Human readable status e.g. `Needs Review`.
+  pub status_name: String,
+  pub author_phid: String,
+  pub reviewers: Vec<Reviewer>,
+  pub repository_phid: Option<String>,
+  pub diff_phid: Option<String>,
+  pub summary: String,
+  pub test_plan: String,
+  pub created_at: u64,
+  pub updated_at: u64,
+}
+
+impl Revision {
+  pub fn from_json(v: &Value) -> Result<Revision, ParseError> {
+    let parser = ObjectParser::new("revision", v);
+
+    let reviewers: Vec<Reviewer> = match parser.get("attachments.reviewers.reviewers") {
+      Value::Array(reviewers) => reviewers
+        .iter()
+        .map(|reviewer| {
+          return Ok(Reviewer {
+            reviewer_phid: parser.string_of(
+              &reviewer["reviewerPHID"],
+              "attachments.reviewers.reviewers[].reviewerPHID",
+            )?,
+            status: parser.string_of(
+              &reviewer["status"],
+              "attachments.reviewers.reviewers[].status",
+            )?,
+            is_blocking: reviewer["isBlocking"].as_bool().unwrap_or(false),
+          });
+        })
+        .collect::<Result<_, ParseError>>()?,
+      _ => vec![],
     };
+
+    return Ok(Revision {
+      id: parser.id()?,
+      phid: parser.string("phid")?,
+      title: parser.string("fields.title")?,
+      status: parser.string("fields.status.value")?,
+      status_name: parser.string("fields.status.name")?,
+      author_phid: parser.string("fields.authorPHID")?,
+      reviewers,
+      repository_phid: parser.optional_string("fields.repositoryPHID")?,
+      diff_phid: parser.optional_string("fields.diffPHID")?,
+      summary: parser
+        .optional_string("fields.summary")?
+        .unwrap_or_default(),
+      test_plan: parser
+        .optional_string("fields.testPlan")?
+        .unwrap_or_default(),
+      created_at: parser.u64("fields.dateCreated")?,
+      updated_at: parser.u64("fields.dateModified")?,
+    });
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Dummy)]
+pub struct Commit {
+  pub id: String,
+  pub phid: String,
+  /// Commit hash.
+  pub identifier: String,
+  pub repository_phid: String,
+  pub message: String,
+  pub author_name: String,
+  pub created_at: u64,
+  pub updated_at: u64,
+}
+
+impl Commit {
+  pub fn from_json(v: &Value) -> Result<Commit, ParseError> {
+    let parser = ObjectParser::new("commit", v);
+
+    return Ok(Commit {
+      id: parser.id()?,
+      phid: parser.string("phid")?,
+      identifier: parser.string("fields.identifier")?,
+      repository_phid: parser.string("fields.repositoryPHID")?,
+      message: parser
+        .optional_string("fields.message")?
+        .unwrap_or_default(),
+      author_name: parser
+        .optional_string("fields.author.name")?
+        .unwrap_or_default(),
+      created_at: parser.u64("fields.dateCreated")?,
+      updated_at: parser.u64("fields.dateModified")?,
+    });
+  }
+
+  /// First line of the commit message.
+  pub fn summary(&self) -> &str {
+    return self.message.lines().next().unwrap_or_default();
+  }
+}
+
+/// Relationship between 2 objects, see `edge.search`.
+#[derive(Clone, Debug, Serialize, Deserialize, Dummy)]
+pub struct Edge {
+  pub source_phid: String,
+  /// Edge type e.g. `task.revision`, `task.commit`, `task.subtask`.
+  pub edge_type: String,
+  pub destination_phid: String,
+}
+
+impl Edge {
+  pub fn from_json(v: &Value) -> Result<Edge, ParseError> {
+    let parser = ObjectParser::new("edge", v);
+
+    return Ok(Edge {
+      source_phid: parser.string("sourcePHID")?,
+      edge_type: parser.string("edgeType")?,
+      destination_phid: parser.string("destinationPHID")?,
+    });
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use serde_json::json;
+
+  fn task_json() -> Value {
+    return json!({
+      "id": 123,
+      "type": "TASK",
+      "phid": "PHID-TASK-123",
+      "fields": {
+        "name": "Fix login",
+        "description": { "raw": "Login is broken" },
+        "authorPHID": "PHID-USER-1",
+        "ownerPHID": null,
+        "status": { "value": "open", "name": "Open" },
+        "priority": { "value": 80, "name": "High" },
+        "points": "2.5",
+        "dateCreated": 1600000000,
+        "dateModified": 1600000001,
+      },
+      "attachments": {
+        "projects": { "projectPHIDs": ["PHID-PROJ-1", "PHID-PROJ-2", "PHID-PROJ-3"] },
+        "columns": {
+          "boards": {
+            "PHID-PROJ-1": {
+              "columns": [{ "id": 9, "phid": "PHID-PCOL-9", "name": "Backlog" }],
+            },
+            "PHID-PROJ-2": {
+              "columns": [{ "id": 12, "phid": "PHID-PCOL-12", "name": "In Review" }],
+            },
+          },
+        },
+      },
+    });
+  }
+
+  #[test]
+  fn test_parse_task() {
+    let task = Task::from_json(&task_json()).unwrap();
+
+    assert_eq!(task.id, "123");
+    assert_eq!(task.assigned_phid, None);
+    assert_eq!(task.point, Some(2.5));
+    assert_eq!(
+      task.project_phids,
+      vec!["PHID-PROJ-1", "PHID-PROJ-2", "PHID-PROJ-3"]
+    );
+
+    let board_names: Vec<&str> = task
+      .boards
+      .iter()
+      .map(|board| board.name.as_str())
+      .collect();
+
+    assert_eq!(board_names, vec!["Backlog", "In Review"]);
+    assert_eq!(task.boards[1].project_phid, "PHID-PROJ-2");
+    // Projects are not fetched yet so boards aren't labeled with them
+    assert_eq!(task.board_labels(), vec!["Backlog", "In Review"]);
+  }
+
+  #[test]
+  fn test_parse_task_with_nullable_fields() {
+    let mut v = task_json();
+    v["fields"]["description"] = Value::Null;
+    v["attachments"] = Value::Null;
+
+    let task = Task::from_json(&v).unwrap();
+
+    assert_eq!(task.description, "");
+    assert!(task.project_phids.is_empty());
+    assert!(task.boards.is_empty());
+  }
+
+  #[test]
+  fn test_parse_task_error_names_field_and_object() {
+    let mut v = task_json();
+    v["fields"]["name"] = json!(12);
+
+    let err = Task::from_json(&v).err().unwrap();
+
+    assert_eq!(err.object_id, "123");
+    assert_eq!(err.field, "fields.name");
+    assert_eq!(
+      err.to_string(),
+      "Could not parse task 123, field fields.name: expected a string, got 12"
+    );
+  }
+
+  fn comment_transaction_json(comments: Value) -> Value {
+    return json!({
+      "id": 7,
+      "phid": "PHID-XACT-TASK-7",
+      "type": "comment",
+      "authorPHID": "PHID-USER-1",
+      "dateCreated": 1600000000,
+      "dateModified": 1600000002,
+      "comments": comments,
+    });
+  }
+
+  #[test]
+  fn test_parse_comment_picks_latest_version() {
+    let v = comment_transaction_json(json!([
+      { "removed": false, "dateModified": 1600000002, "content": { "raw": "Fixed typo" } },
+      { "removed": false, "dateModified": 1600000000, "content": { "raw": "Fixd typo" } },
+    ]));
+
+    let comment = Comment::from_transaction_json(&v).unwrap().unwrap();
+
+    assert_eq!(comment.id, "7");
+    assert_eq!(comment.author_phid, "PHID-USER-1");
+    assert_eq!(comment.content, "Fixed typo");
+    assert_eq!(comment.created_at, 1600000000);
+    assert_eq!(comment.updated_at, 1600000002);
+  }
+
+  #[test]
+  fn test_parse_comment_skips_removed_and_non_comment_transactions() {
+    let removed = comment_transaction_json(json!([
+      { "removed": true, "dateModified": 1600000002, "content": { "raw": "" } },
+      { "removed": false, "dateModified": 1600000000, "content": { "raw": "Oops" } },
+    ]));
+
+    let mut status_change = comment_transaction_json(json!([]));
+    status_change["type"] = json!("status");
+
+    assert!(Comment::from_transaction_json(&removed).unwrap().is_none());
+    assert!(Comment::from_transaction_json(&status_change)
+      .unwrap()
+      .is_none());
+  }
+
+  fn revision_json() -> Value {
+    return json!({
+      "id": 42,
+      "phid": "PHID-DREV-42",
+      "fields": {
+        "title": "Fix login",
+        "status": { "value": "needs-review", "name": "Needs Review" },
+        "authorPHID": "PHID-USER-1",
+        "repositoryPHID": "PHID-REPO-1",
+        "diffPHID": "PHID-DIFF-7",
+        "summary": "Login is broken",
+        "testPlan": "Logged in",
+        "dateCreated": 1600000000,
+        "dateModified": 1600000001,
+      },
+      "attachments": {
+        "reviewers": {
+          "reviewers": [
+            { "reviewerPHID": "PHID-USER-2", "status": "accepted", "isBlocking": false },
+            { "reviewerPHID": "PHID-PROJ-1", "status": "blocking", "isBlocking": true },
+          ],
+        },
+      },
+    });
+  }
+
+  #[test]
+  fn test_parse_revision_reviewers() {
+    let revision = Revision::from_json(&revision_json()).unwrap();
+
+    assert_eq!(revision.id, "42");
+    assert_eq!(revision.status, "needs-review");
+    assert_eq!(revision.status_name, "Needs Review");
+    assert_eq!(revision.repository_phid.as_deref(), Some("PHID-REPO-1"));
+    assert_eq!(revision.diff_phid.as_deref(), Some("PHID-DIFF-7"));
+
+    let reviewers: Vec<(&str, &str, bool)> = revision
+      .reviewers
+      .iter()
+      .map(|reviewer| {
+        return (
+          reviewer.reviewer_phid.as_str(),
+          reviewer.status.as_str(),
+          reviewer.is_blocking,
+        );
+      })
+      .collect();
+
+    assert_eq!(
+      reviewers,
+      vec![
+        ("PHID-USER-2", "accepted", false),
+        ("PHID-PROJ-1", "blocking", true),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_parse_revision_with_nullable_fields() {
+    let mut v = revision_json();
+    v["fields"]["repositoryPHID"] = Value::Null;
+    v["fields"]["diffPHID"] = Value::Null;
+    v["attachments"] = json!({});
+
+    let revision = Revision::from_json(&v).unwrap();
+
+    assert_eq!(revision.repository_phid, None);
+    assert_eq!(revision.diff_phid, None);
+    assert!(revision.reviewers.is_empty());
   }
 }
@@ -1,3 +1,5 @@
+#![allow(clippy::needless_return)]
+
 #[macro_use]
 pub mod utils;
 
@@ -1,2 +1,3 @@
+#[allow(clippy::module_inception)]
 pub mod storage;
 pub mod storage_fs;
@@ -3,9 +3,7 @@ use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
 
-use anyhow::Error;
 use slugify::slugify;
-use thiserror::Error;
 
 use crate::dto::Task;
 use crate::dto::Watchlist;
@@ -35,10 +33,7 @@ impl PhabStorageFilesystem {
 
 impl PhabStorageFilesystem {
   fn watchlist_table(&mut self) -> &mut Table {
-    return self
-      .db_content
-      .entry("watchlists".to_owned())
-      .or_insert(HashMap::new());
+    return self.db_content.entry("watchlists".to_owned()).or_default();
   }
 
   fn reload(&mut self) -> ResultAnyError<()> {
@@ -71,21 +66,6 @@ impl PhabStorageFilesystem {
   }
 }
 
-#[derive(Debug, Error)]
-enum PhabStorageFilesystemError {
-  #[error("PhabStorageFilesystemError err: {message:?}")]
-  QueryError { message: String },
-}
-
-impl PhabStorageFilesystemError {
-  fn query_error(message: &str) -> Error {
-    return PhabStorageFilesystemError::QueryError {
-      message: message.to_owned(),
-    }
-    .into();
-  }
-}
-
 impl PhabStorage for PhabStorageFilesystem {
   fn add_to_watchlist(&mut self, watchlist_id: &str, task: &Task) -> ResultAnyError<()> {
     self
@@ -120,7 +100,7 @@ impl PhabStorage for PhabStorageFilesystem {
   }
 
   fn get_watchlist_by_id(&mut self, watchlist_id: &str) -> ResultAnyError<Option<Watchlist>> {
-    let watchlist = self.watchlist_table().get(watchlist_id).map(Clone::clone);
+    let watchlist = self.watchlist_table().get(watchlist_id).cloned();
 
     return Ok(watchlist);
   }
@@ -200,7 +180,7 @@ mod test {
 
       assert_eq!(watchlists.len(), 1);
       assert_eq!(
-        watchlists.get(0).unwrap().id.as_ref().unwrap(),
+        watchlists.first().unwrap().id.as_ref().unwrap(),
         "hey-ho-test-watchlist"
       );
 
@@ -208,7 +188,7 @@ mod test {
       storage.reload()?;
       assert_eq!(watchlists.len(), 1);
       assert_eq!(
-        watchlists.get(0).unwrap().id.as_ref().unwrap(),
+        watchlists.first().unwrap().id.as_ref().unwrap(),
         "hey-ho-test-watchlist"
       );
 
@@ -246,7 +226,7 @@ mod test {
       let tasks: Vec<Task> = watchlist.unwrap().tasks;
 
       assert_eq!(tasks.len(), 2);
-      assert_eq!(tasks.get(0).unwrap().id, "foo");
+      assert_eq!(tasks.first().unwrap().id, "foo");
       assert_eq!(tasks.get(1).unwrap().id, "Bar");
 
       return Ok(());
@@ -0,0 +1,60 @@
+/// Lowercase alphanumerics only, so `in-review` and `In Review` are the same name.
+fn normalize(name: &str) -> String {
+  return name
+    .chars()
+    .filter(|c| c.is_alphanumeric())
+    .flat_map(char::to_lowercase)
+    .collect();
+}
+
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+  let mut haystack_chars = haystack.chars();
+
+  return needle
+    .chars()
+    .all(|needle_char| haystack_chars.any(|c| c == needle_char));
+}
+
+/// Find items whose name matches the query, from the strictest match to the loosest one:
+/// case insensitive equality, equality ignoring punctuation and spaces, substring and
+/// finally subsequence (`bklg` matches `Backlog`). Only the strictest matches are returned,
+/// so more than one item means the query is ambiguous.
+/// ```
+/// use phab_lib::utils::fuzzy::fuzzy_match;
+///
+/// let columns = vec!["Backlog", "In Progress", "In Review", "Review Later"];
+/// let matches = |query| fuzzy_match(&columns, query, |column| column);
+///
+/// assert_eq!(matches("in review"), vec![&"In Review"]);
+/// assert_eq!(matches("in-review"), vec![&"In Review"]);
+/// assert_eq!(matches("log"), vec![&"Backlog"]);
+/// assert_eq!(matches("bklg"), vec![&"Backlog"]);
+/// assert_eq!(matches("review"), vec![&"In Review", &"Review Later"]);
+/// assert!(matches("done").is_empty());
+/// ```
+pub fn fuzzy_match<'a, T>(items: &'a [T], query: &str, name_of: impl Fn(&T) -> &str) -> Vec<&'a T> {
+  let normalized_query = normalize(query);
+  let matchers: [&dyn Fn(&str) -> bool; 4] = [
+    &|name| name.eq_ignore_ascii_case(query),
+    &|name| normalize(name) == normalized_query,
+    &|name| normalize(name).contains(&normalized_query),
+    &|name| is_subsequence(&normalized_query, &normalize(name)),
+  ];
+
+  if normalized_query.is_empty() {
+    return items
+      .iter()
+      .filter(|item| name_of(item).eq_ignore_ascii_case(query))
+      .collect();
+  }
+
+  for matcher in matchers.iter() {
+    let matches: Vec<&T> = items.iter().filter(|item| matcher(name_of(item))).collect();
+
+    if !matches.is_empty() {
+      return matches;
+    }
+  }
+
+  return vec![];
+}
@@ -1,4 +1,5 @@
 // https://stackoverflow.com/questions/38088067/equivalent-of-func-or-function-in-rust
+#[allow(unused_macros)]
 macro_rules! function_name {
   () => {{
     fn f() {}
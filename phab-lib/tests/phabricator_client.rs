@@ -0,0 +1,520 @@
+#![allow(clippy::needless_return)]
+
+mod support;
+
+use phab_lib::client::cassette::RecordingTransport;
+use phab_lib::client::cassette::ReplayTransport;
+use phab_lib::client::phabricator::ErrorType;
+use phab_lib::client::phabricator::PhabricatorClient;
+use phab_lib::client::task_edit::TaskTransaction;
+use phab_lib::client::task_query::TaskQuery;
+use phab_lib::client::transport::HttpTransport;
+
+use support::task_phid;
+use support::user_phid;
+use support::FakeColumn;
+use support::FakeConduit;
+use support::FakeConduitServer;
+use support::FakeFailure;
+use support::FakeProject;
+use support::FakeTask;
+use support::FakeUser;
+use support::API_TOKEN;
+
+fn conduit_error_code(err: &anyhow::Error) -> Option<&str> {
+  return match err.downcast_ref::<ErrorType>() {
+    Some(ErrorType::ConduitError { code, .. }) => Some(code.as_str()),
+    _ => None,
+  };
+}
+
+#[tokio::test]
+async fn test_search_tasks_follows_cursor_until_last_page() {
+  let tasks = (1..=5)
+    .map(|id| FakeTask::new(id, &format!("Task {}", id)))
+    .collect();
+
+  let server = FakeConduitServer::start(FakeConduit::new().with_tasks(tasks).with_page_size(2));
+  let phabricator = PhabricatorClient::new(server.client_config()).unwrap();
+
+  let tasks = phabricator.search_tasks(&TaskQuery::new()).await.unwrap();
+  let task_ids: Vec<&str> = tasks.iter().map(|task| task.id.as_str()).collect();
+
+  assert_eq!(task_ids, vec!["1", "2", "3", "4", "5"]);
+  assert_eq!(
+    server
+      .conduit
+      .lock()
+      .unwrap()
+      .requests_of("maniphest.search")
+      .len(),
+    3
+  );
+
+  let tasks = phabricator
+    .search_tasks(&TaskQuery::new().limit(3))
+    .await
+    .unwrap();
+
+  assert_eq!(tasks.len(), 3);
+}
+
+#[tokio::test]
+async fn test_get_task_family_fetches_every_subtask_level() {
+  let tasks = vec![
+    FakeTask::new(1, "Epic"),
+    FakeTask::new(2, "Story").child_of(1),
+    FakeTask::new(3, "Sub story").child_of(2),
+    FakeTask::new(4, "Another story").child_of(1),
+    FakeTask::new(5, "Unrelated"),
+  ];
+
+  let server = FakeConduitServer::start(FakeConduit::new().with_tasks(tasks));
+  let phabricator = PhabricatorClient::new(server.client_config()).unwrap();
+
+  let task_family = phabricator
+    .get_task_family("T1", None)
+    .await
+    .unwrap()
+    .unwrap();
+
+  let task_ids: Vec<&str> = task_family
+    .tasks()
+    .into_iter()
+    .map(|task| task.id.as_str())
+    .collect();
+
+  assert_eq!(task_ids, vec!["1", "2", "3", "4"]);
+  assert_eq!(
+    task_family.children[0].children[0].parent_task.name,
+    "Sub story"
+  );
+
+  let missing = phabricator.get_task_family("T404", None).await.unwrap();
+
+  assert!(missing.is_none());
+}
+
+#[tokio::test]
+async fn test_create_and_edit_task() {
+  let server =
+    FakeConduitServer::start(FakeConduit::new().with_tasks(vec![FakeTask::new(1, "Epic")]));
+  let phabricator = PhabricatorClient::new(server.client_config()).unwrap();
+
+  let task = phabricator
+    .create_task(vec![
+      TaskTransaction::Title("Fix login".into()),
+      TaskTransaction::Parent(task_phid(1)),
+      TaskTransaction::Points(Some(2.5)),
+    ])
+    .await
+    .unwrap();
+
+  assert_eq!(task.id, "2");
+  assert_eq!(task.name, "Fix login");
+  assert_eq!(task.point, Some(2.5));
+
+  let task = phabricator
+    .edit_task(
+      "T2",
+      vec![
+        TaskTransaction::Owner(Some(user_phid(7))),
+        TaskTransaction::Status("resolved".into()),
+      ],
+    )
+    .await
+    .unwrap();
+
+  assert_eq!(task.assigned_phid, Some(user_phid(7)));
+  assert_eq!(task.status, "resolved");
+
+  let task = phabricator
+    .edit_task("T2", vec![TaskTransaction::Owner(None)])
+    .await
+    .unwrap();
+
+  assert_eq!(task.assigned_phid, None);
+  assert_eq!(
+    server.conduit.lock().unwrap().task(2).unwrap().parent_ids,
+    vec![1]
+  );
+}
+
+#[tokio::test]
+async fn test_search_users_and_projects() {
+  let conduit = FakeConduit::new()
+    .with_users(vec![FakeUser::new(1, "alice"), FakeUser::new(2, "bob")])
+    .with_projects(vec![
+      FakeProject::new(1, "Mobile App"),
+      FakeProject::new(2, "Web App"),
+      FakeProject::new(3, "Infrastructure"),
+    ]);
+
+  let server = FakeConduitServer::start(conduit);
+  let phabricator = PhabricatorClient::new(server.client_config()).unwrap();
+
+  let users = phabricator
+    .get_users_by_phids(vec![&user_phid(2)])
+    .await
+    .unwrap();
+
+  assert_eq!(users.len(), 1);
+  assert_eq!(users[0].username, "bob");
+
+  let current_user = phabricator.get_current_user().await.unwrap();
+
+  assert_eq!(current_user.username, "alice");
+
+  let projects = phabricator.search_projects_by_name("app").await.unwrap();
+  let project_names: Vec<&str> = projects
+    .iter()
+    .map(|project| project.name.as_str())
+    .collect();
+
+  assert_eq!(project_names, vec!["Mobile App", "Web App"]);
+}
+
+#[tokio::test]
+async fn test_conduit_errors() {
+  let server =
+    FakeConduitServer::start(FakeConduit::new().with_tasks(vec![FakeTask::new(1, "Epic")]));
+  let phabricator = PhabricatorClient::new(server.client_config()).unwrap();
+
+  server.conduit.lock().unwrap().fail_next(
+    "maniphest.search",
+    FakeFailure::Conduit {
+      code: "ERR-CONDUIT-CORE".into(),
+      info: "Query overheated.".into(),
+    },
+  );
+
+  let err = phabricator.get_task_by_id("T1").await.err().unwrap();
+
+  assert_eq!(conduit_error_code(&err), Some("ERR-CONDUIT-CORE"));
+  assert_eq!(err.to_string(), "ERR-CONDUIT-CORE: Query overheated.");
+
+  let err = phabricator
+    .edit_task("T404", vec![TaskTransaction::Title("foo".into())])
+    .await
+    .err()
+    .unwrap();
+
+  assert_eq!(conduit_error_code(&err), Some("ERR-CONDUIT-CORE"));
+
+  let mut config = server.client_config();
+  config.api_token = "invalid".into();
+
+  let err = PhabricatorClient::new(config)
+    .unwrap()
+    .get_task_by_id("T1")
+    .await
+    .err()
+    .unwrap();
+
+  assert_eq!(conduit_error_code(&err), Some("ERR-INVALID-AUTH"));
+}
+
+#[tokio::test]
+async fn test_only_read_methods_are_retried_on_unavailable_server() {
+  let server =
+    FakeConduitServer::start(FakeConduit::new().with_tasks(vec![FakeTask::new(1, "Epic")]));
+  let phabricator = PhabricatorClient::new(server.client_config()).unwrap();
+
+  {
+    let mut conduit = server.conduit.lock().unwrap();
+    conduit.fail_next("maniphest.search", FakeFailure::Http(503));
+    conduit.fail_next("maniphest.edit", FakeFailure::Http(503));
+  }
+
+  let task = phabricator.get_task_by_id("T1").await.unwrap();
+
+  assert!(task.is_some());
+  assert_eq!(
+    server
+      .conduit
+      .lock()
+      .unwrap()
+      .requests_of("maniphest.search")
+      .len(),
+    2
+  );
+
+  let err = phabricator
+    .edit_task("T1", vec![TaskTransaction::Title("foo".into())])
+    .await
+    .err()
+    .unwrap();
+
+  assert!(err.to_string().contains("http status 503"));
+  assert_eq!(
+    server.conduit.lock().unwrap().task(1).unwrap().title,
+    "Epic"
+  );
+}
+
+#[tokio::test]
+async fn test_task_family_users_are_resolved_with_one_cached_search() {
+  let mut owned_task = FakeTask::new(2, "Story").child_of(1);
+  owned_task.owner_phid = Some(user_phid(2));
+
+  let conduit = FakeConduit::new()
+    .with_tasks(vec![FakeTask::new(1, "Epic"), owned_task])
+    .with_users(vec![FakeUser::new(1, "alice"), FakeUser::new(2, "bob")]);
+
+  let server = FakeConduitServer::start(conduit);
+  let phabricator = PhabricatorClient::new(server.client_config()).unwrap();
+
+  let mut task_family = phabricator
+    .get_task_family("T1", None)
+    .await
+    .unwrap()
+    .unwrap();
+
+  phabricator
+    .fetch_task_family_users(&mut task_family)
+    .await
+    .unwrap();
+
+  let child = &task_family.children[0].parent_task;
+
+  assert_eq!(child.author.as_ref().unwrap().username, "alice");
+  assert_eq!(child.assigned.as_ref().unwrap().username, "bob");
+  assert!(task_family.parent_task.assigned.is_none());
+
+  let users = phabricator
+    .get_users_by_phids(vec![&user_phid(2), &user_phid(1)])
+    .await
+    .unwrap();
+
+  assert_eq!(users[0].username, "bob");
+  assert_eq!(
+    server
+      .conduit
+      .lock()
+      .unwrap()
+      .requests_of("user.search")
+      .len(),
+    1
+  );
+}
+
+#[tokio::test]
+async fn test_task_boards_and_projects() {
+  let backlog = FakeColumn::new(1, "Backlog", 1);
+  let in_review = FakeColumn::new(2, "In Review", 2);
+
+  let conduit = FakeConduit::new()
+    .with_projects(vec![
+      FakeProject::new(1, "Backend"),
+      FakeProject::new(2, "Mobile App"),
+    ])
+    .with_columns(vec![backlog.clone(), in_review.clone()])
+    .with_tasks(vec![
+      FakeTask::new(1, "Epic")
+        .in_column(&backlog)
+        .in_column(&in_review),
+      FakeTask::new(2, "Untagged"),
+    ]);
+
+  let server = FakeConduitServer::start(conduit);
+  let phabricator = PhabricatorClient::new(server.client_config()).unwrap();
+
+  let mut tasks = phabricator.get_tasks_by_ids(vec!["1", "2"]).await.unwrap();
+
+  phabricator
+    .fetch_tasks_projects(tasks.iter_mut().collect())
+    .await
+    .unwrap();
+
+  let task = &tasks[0];
+  let boards: Vec<(&str, &str)> = task
+    .boards
+    .iter()
+    .map(|board| {
+      (
+        task.board_project(board).unwrap().name.as_str(),
+        board.name.as_str(),
+      )
+    })
+    .collect();
+
+  assert_eq!(
+    boards,
+    vec![("Backend", "Backlog"), ("Mobile App", "In Review")]
+  );
+  assert_eq!(task.projects.len(), 2);
+  assert!(tasks[1].boards.is_empty());
+  assert!(tasks[1].projects.is_empty());
+
+  let columns = phabricator
+    .get_project_columns(vec![&support::project_phid(2)])
+    .await
+    .unwrap();
+
+  assert_eq!(columns.len(), 1);
+  assert_eq!(columns[0].name, "In Review");
+  assert_eq!(columns[0].project_phid, support::project_phid(2));
+}
+
+#[tokio::test]
+async fn test_workboard_lanes_follow_visible_columns() {
+  let backlog = FakeColumn::new(1, "Backlog", 1);
+  let in_review = FakeColumn::new(2, "In Review", 1);
+  let archived = FakeColumn::new(3, "Archived", 1).hidden();
+
+  let mut resolved_task = FakeTask::new(3, "Resolved").in_column(&in_review);
+  let mut owned_task = FakeTask::new(2, "Owned").in_column(&in_review);
+
+  resolved_task.status = String::from("resolved");
+  owned_task.owner_phid = Some(user_phid(2));
+
+  let conduit = FakeConduit::new()
+    .with_users(vec![FakeUser::new(1, "alice"), FakeUser::new(2, "bob")])
+    .with_projects(vec![FakeProject::new(1, "Backend")])
+    .with_columns(vec![backlog.clone(), in_review, archived.clone()])
+    .with_tasks(vec![
+      FakeTask::new(1, "Todo").in_column(&backlog),
+      owned_task,
+      resolved_task,
+      FakeTask::new(4, "Old").in_column(&archived),
+    ]);
+
+  let server = FakeConduitServer::start(conduit);
+  let phabricator = PhabricatorClient::new(server.client_config()).unwrap();
+  let project_phid = support::project_phid(1);
+
+  let workboard = phabricator
+    .get_workboard(&project_phid, false)
+    .await
+    .unwrap();
+  let lanes: Vec<(&str, Vec<&str>)> = workboard
+    .lanes
+    .iter()
+    .map(|lane| {
+      (
+        lane.column.name.as_str(),
+        lane.tasks.iter().map(|task| task.id.as_str()).collect(),
+      )
+    })
+    .collect();
+
+  assert_eq!(workboard.project.name, "Backend");
+  assert_eq!(
+    lanes,
+    vec![("Backlog", vec!["1"]), ("In Review", vec!["2"])]
+  );
+  assert_eq!(
+    workboard.lanes[1].tasks[0]
+      .assigned
+      .as_ref()
+      .unwrap()
+      .username,
+    "bob"
+  );
+
+  let workboard = phabricator
+    .get_workboard(&project_phid, true)
+    .await
+    .unwrap();
+
+  assert_eq!(workboard.lanes[1].tasks.len(), 2);
+}
+
+#[tokio::test]
+async fn test_move_task_to_fuzzy_matched_column() {
+  let backend_backlog = FakeColumn::new(1, "Backlog", 1);
+  let mobile_backlog = FakeColumn::new(3, "Backlog", 2);
+
+  let conduit = FakeConduit::new()
+    .with_projects(vec![
+      FakeProject::new(1, "Backend"),
+      FakeProject::new(2, "Mobile App"),
+    ])
+    .with_columns(vec![
+      backend_backlog.clone(),
+      FakeColumn::new(2, "In Review", 1),
+      mobile_backlog.clone(),
+      FakeColumn::new(4, "Done", 2),
+    ])
+    .with_tasks(vec![FakeTask::new(1, "Epic")
+      .in_column(&backend_backlog)
+      .in_column(&mobile_backlog)]);
+
+  let server = FakeConduitServer::start(conduit);
+  let phabricator = PhabricatorClient::new(server.client_config()).unwrap();
+
+  let task = phabricator
+    .move_task_to_column("T1", "in-review", None)
+    .await
+    .unwrap();
+
+  let column_names: Vec<&str> = task
+    .boards
+    .iter()
+    .map(|board| board.name.as_str())
+    .collect();
+
+  assert_eq!(column_names, vec!["In Review", "Backlog"]);
+  assert_eq!(
+    server.conduit.lock().unwrap().task(1).unwrap().column_ids,
+    vec![3, 2]
+  );
+
+  let err = phabricator
+    .move_task_to_column("T1", "backlog", None)
+    .await
+    .err()
+    .unwrap();
+
+  assert_eq!(
+    err.to_string(),
+    "Validation error: Column name backlog is ambiguous, matching columns: Backend: Backlog, Mobile App: Backlog"
+  );
+
+  phabricator
+    .move_task_to_column("T1", "backlog", Some("mobile"))
+    .await
+    .unwrap();
+
+  let err = phabricator
+    .move_task_to_column("T1", "shipped", Some("Backend"))
+    .await
+    .err()
+    .unwrap();
+
+  assert_eq!(
+    err.to_string(),
+    "Validation error: Could not find column shipped, valid columns: Backend: Backlog, Backend: In Review"
+  );
+}
+
+#[tokio::test]
+async fn test_recorded_cassette_replays_without_the_api_token() {
+  let server = FakeConduitServer::start(
+    FakeConduit::new().with_tasks(vec![FakeTask::new(1, "Login"), FakeTask::new(2, "Logout")]),
+  );
+
+  let path = std::env::temp_dir().join(format!("phab-http-cassette-{}.json", std::process::id()));
+  let transport = HttpTransport::new(server.client_config()).unwrap();
+  let phabricator = PhabricatorClient::with_transport(RecordingTransport::new(transport, &path));
+
+  let recorded_tasks = phabricator
+    .get_tasks_by_ids(vec!["T1", "T2"])
+    .await
+    .unwrap();
+  let content = std::fs::read_to_string(&path).unwrap();
+
+  assert!(!content.contains(API_TOKEN));
+
+  let phabricator = PhabricatorClient::with_transport(ReplayTransport::from_file(&path).unwrap());
+
+  std::fs::remove_file(&path).unwrap();
+
+  let replayed_tasks = phabricator
+    .get_tasks_by_ids(vec!["T1", "T2"])
+    .await
+    .unwrap();
+
+  assert_eq!(replayed_tasks.len(), recorded_tasks.len());
+  assert_eq!(replayed_tasks[1].name, "Logout");
+}
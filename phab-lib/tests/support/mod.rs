@@ -0,0 +1,939 @@
+//! Local fake conduit server, it answers conduit http calls from an in-memory
+//! dataset with the same json shapes as phabricator so `PhabricatorClient`
+//! can be tested end to end without a phabricator install.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use hyper::service::make_service_fn;
+use hyper::service::service_fn;
+use hyper::Body;
+use hyper::Request;
+use hyper::Response;
+use hyper::Server;
+use hyper::StatusCode;
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
+
+use phab_lib::client::config::PhabricatorClientConfig;
+use phab_lib::client::config::RetryConfig;
+
+pub const API_TOKEN: &str = "api-test-token";
+
+/// Phabricator caps the page size at 100.
+const MAX_PAGE_SIZE: usize = 100;
+
+#[derive(Clone, Debug)]
+pub struct FakeTask {
+  pub id: u64,
+  pub title: String,
+  pub description: String,
+  pub author_phid: String,
+  pub owner_phid: Option<String>,
+  /// Status keyword e.g. `open`, `resolved`.
+  pub status: String,
+  /// Priority keyword e.g. `high`, `normal`.
+  pub priority: String,
+  pub points: Option<f64>,
+  pub project_phids: Vec<String>,
+  pub parent_ids: Vec<u64>,
+  /// Workboard columns the task is in, one per project board.
+  pub column_ids: Vec<u64>,
+  pub date_modified: u64,
+}
+
+impl FakeTask {
+  pub fn new(id: u64, title: &str) -> FakeTask {
+    return FakeTask {
+      id,
+      title: title.to_owned(),
+      description: String::new(),
+      author_phid: user_phid(1),
+      owner_phid: None,
+      status: String::from("open"),
+      priority: String::from("normal"),
+      points: None,
+      project_phids: vec![],
+      parent_ids: vec![],
+      column_ids: vec![],
+      date_modified: 1600000000 + id,
+    };
+  }
+
+  pub fn child_of(mut self, parent_id: u64) -> FakeTask {
+    self.parent_ids.push(parent_id);
+    return self;
+  }
+
+  /// Put the task in the column and tag it with the column project.
+  pub fn in_column(mut self, column: &FakeColumn) -> FakeTask {
+    self.column_ids.push(column.id);
+
+    if !self
+      .project_phids
+      .contains(&project_phid(column.project_id))
+    {
+      self.project_phids.push(project_phid(column.project_id));
+    }
+
+    return self;
+  }
+
+  pub fn phid(&self) -> String {
+    return task_phid(self.id);
+  }
+
+  fn to_json(&self, columns: &[FakeColumn]) -> Value {
+    let (priority_value, priority_name) = priority_of(&self.priority);
+    let mut boards = Map::new();
+
+    for column in columns
+      .iter()
+      .filter(|column| self.column_ids.contains(&column.id))
+    {
+      boards.insert(
+        project_phid(column.project_id),
+        json!({ "columns": [{ "id": column.id, "phid": column.phid(), "name": column.name }] }),
+      );
+    }
+
+    // Phabricator encodes an empty map as an empty list
+    let boards = if boards.is_empty() {
+      json!([])
+    } else {
+      Value::Object(boards)
+    };
+
+    return json!({
+      "id": self.id,
+      "type": "TASK",
+      "phid": self.phid(),
+      "fields": {
+        "name": self.title,
+        "description": { "raw": self.description },
+        "authorPHID": self.author_phid,
+        "ownerPHID": self.owner_phid,
+        "status": {
+          "value": self.status,
+          "name": status_name_of(&self.status),
+          "color": null,
+        },
+        "priority": {
+          "value": priority_value,
+          "name": priority_name,
+          "color": "sky",
+        },
+        // Phabricator returns points as a numeric string
+        "points": self.points.map(|points| points.to_string()),
+        "subtype": "default",
+        "closerPHID": null,
+        "dateClosed": null,
+        "spacePHID": null,
+        "dateCreated": 1600000000 + self.id,
+        "dateModified": self.date_modified,
+        "policy": { "view": "users", "interact": "users", "edit": "users" },
+      },
+      "attachments": {
+        "projects": { "projectPHIDs": self.project_phids },
+        "columns": { "boards": boards },
+      },
+    });
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct FakeUser {
+  pub id: u64,
+  pub username: String,
+  pub real_name: String,
+}
+
+impl FakeUser {
+  pub fn new(id: u64, username: &str) -> FakeUser {
+    return FakeUser {
+      id,
+      username: username.to_owned(),
+      real_name: format!("{} real name", username),
+    };
+  }
+
+  fn to_json(&self) -> Value {
+    return json!({
+      "id": self.id,
+      "type": "USER",
+      "phid": user_phid(self.id),
+      "fields": {
+        "username": self.username,
+        "realName": self.real_name,
+        "roles": ["verified", "approved", "activated"],
+        "dateCreated": 1500000000 + self.id,
+        "dateModified": 1500000000 + self.id,
+        "policy": { "view": "public", "edit": "no-one" },
+      },
+      "attachments": {},
+    });
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct FakeProject {
+  pub id: u64,
+  pub name: String,
+}
+
+impl FakeProject {
+  pub fn new(id: u64, name: &str) -> FakeProject {
+    return FakeProject {
+      id,
+      name: name.to_owned(),
+    };
+  }
+
+  fn to_json(&self) -> Value {
+    return json!({
+      "id": self.id,
+      "type": "PROJ",
+      "phid": project_phid(self.id),
+      "fields": {
+        "name": self.name,
+        "slug": self.name.to_lowercase().replace(' ', "_"),
+        "milestone": null,
+        "depth": 0,
+        "parent": null,
+        "icon": { "key": "project", "name": "Project", "icon": "fa-briefcase" },
+        "color": { "key": "blue", "name": "Blue" },
+        "description": "",
+        "dateCreated": 1500000000 + self.id,
+        "dateModified": 1500000000 + self.id,
+        "policy": { "view": "users", "edit": "users", "join": "users" },
+      },
+      "attachments": {},
+    });
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct FakeColumn {
+  pub id: u64,
+  pub name: String,
+  pub project_id: u64,
+  pub is_hidden: bool,
+}
+
+impl FakeColumn {
+  pub fn new(id: u64, name: &str, project_id: u64) -> FakeColumn {
+    return FakeColumn {
+      id,
+      name: name.to_owned(),
+      project_id,
+      is_hidden: false,
+    };
+  }
+
+  pub fn hidden(mut self) -> FakeColumn {
+    self.is_hidden = true;
+    return self;
+  }
+
+  pub fn phid(&self) -> String {
+    return column_phid(self.id);
+  }
+
+  fn to_json(&self, projects: &[FakeProject]) -> Value {
+    let project_name = projects
+      .iter()
+      .find(|project| project.id == self.project_id)
+      .map(|project| project.name.clone())
+      .unwrap_or_default();
+
+    return json!({
+      "id": self.id,
+      "type": "PCOL",
+      "phid": self.phid(),
+      "fields": {
+        "name": self.name,
+        "proxyPHID": null,
+        "project": {
+          "id": self.project_id,
+          "phid": project_phid(self.project_id),
+          "name": project_name,
+        },
+        "isHidden": self.is_hidden,
+        "dateCreated": 1500000000 + self.id,
+        "dateModified": 1500000000 + self.id,
+        "policy": { "view": "users", "edit": "users" },
+      },
+      "attachments": {},
+    });
+  }
+}
+
+pub fn task_phid(id: u64) -> String {
+  return format!("PHID-TASK-{}", id);
+}
+
+pub fn user_phid(id: u64) -> String {
+  return format!("PHID-USER-{}", id);
+}
+
+pub fn project_phid(id: u64) -> String {
+  return format!("PHID-PROJ-{}", id);
+}
+
+pub fn column_phid(id: u64) -> String {
+  return format!("PHID-PCOL-{}", id);
+}
+
+fn status_name_of(status: &str) -> &'static str {
+  return match status {
+    "resolved" => "Resolved",
+    "wontfix" => "Wontfix",
+    "invalid" => "Invalid",
+    _ => "Open",
+  };
+}
+
+fn priority_of(priority: &str) -> (u64, &'static str) {
+  return match priority {
+    "unbreak" => (100, "Unbreak Now!"),
+    "triage" => (90, "Needs Triage"),
+    "high" => (80, "High"),
+    "low" => (25, "Low"),
+    "wish" => (0, "Wishlist"),
+    _ => (50, "Normal"),
+  };
+}
+
+/// Failure that the server responds with for the next call of a method.
+#[derive(Clone, Debug)]
+pub enum FakeFailure {
+  Http(u16),
+  Conduit { code: String, info: String },
+}
+
+/// In-memory phabricator data, it's shared with the running server
+/// so tests can inspect it after calling the server.
+#[derive(Default)]
+pub struct FakeConduit {
+  pub tasks: Vec<FakeTask>,
+  pub users: Vec<FakeUser>,
+  pub projects: Vec<FakeProject>,
+  pub columns: Vec<FakeColumn>,
+  pub comments: Vec<(u64, String)>,
+  /// Server page size, the request `limit` can only make it smaller.
+  pub page_size: usize,
+  /// Calls that were received as (method, params), oldest first.
+  pub requests: Vec<(String, Value)>,
+  failures: HashMap<String, Vec<FakeFailure>>,
+}
+
+impl FakeConduit {
+  pub fn new() -> FakeConduit {
+    return FakeConduit {
+      page_size: MAX_PAGE_SIZE,
+      ..FakeConduit::default()
+    };
+  }
+
+  pub fn with_tasks(mut self, tasks: Vec<FakeTask>) -> FakeConduit {
+    self.tasks = tasks;
+    return self;
+  }
+
+  pub fn with_users(mut self, users: Vec<FakeUser>) -> FakeConduit {
+    self.users = users;
+    return self;
+  }
+
+  pub fn with_projects(mut self, projects: Vec<FakeProject>) -> FakeConduit {
+    self.projects = projects;
+    return self;
+  }
+
+  pub fn with_columns(mut self, columns: Vec<FakeColumn>) -> FakeConduit {
+    self.columns = columns;
+    return self;
+  }
+
+  pub fn with_page_size(mut self, page_size: usize) -> FakeConduit {
+    self.page_size = page_size;
+    return self;
+  }
+
+  /// Fail the next calls of `method`, one failure per call.
+  pub fn fail_next(&mut self, method: &str, failure: FakeFailure) {
+    self
+      .failures
+      .entry(method.to_owned())
+      .or_default()
+      .push(failure);
+  }
+
+  pub fn requests_of(&self, method: &str) -> Vec<&Value> {
+    return self
+      .requests
+      .iter()
+      .filter(|(request_method, _)| request_method == method)
+      .map(|(_, params)| params)
+      .collect();
+  }
+
+  pub fn task(&self, id: u64) -> Option<&FakeTask> {
+    return self.tasks.iter().find(|task| task.id == id);
+  }
+
+  fn handle(&mut self, method: &str, params: Value) -> (StatusCode, Value) {
+    self.requests.push((method.to_owned(), params.clone()));
+
+    let failure = self
+      .failures
+      .get_mut(method)
+      .filter(|failures| !failures.is_empty())
+      .map(|failures| failures.remove(0));
+
+    let result = match failure {
+      Some(FakeFailure::Http(status)) => {
+        return (
+          StatusCode::from_u16(status).unwrap(),
+          json!("Service unavailable"),
+        );
+      }
+      Some(FakeFailure::Conduit { code, info }) => Err((code, info)),
+      None => self.dispatch(method, &params),
+    };
+
+    let body = match result {
+      Ok(result) => json!({ "result": result, "error_code": null, "error_info": null }),
+      Err((code, info)) => json!({ "result": null, "error_code": code, "error_info": info }),
+    };
+
+    return (StatusCode::OK, body);
+  }
+
+  fn dispatch(&mut self, method: &str, params: &Value) -> Result<Value, (String, String)> {
+    return match method {
+      "user.whoami" => Ok(json!({ "phid": user_phid(1), "userName": "admin" })),
+      "maniphest.search" => Ok(self.search_tasks(params)),
+      "maniphest.edit" => self.edit_task(params),
+      "user.search" => Ok(self.search_users(params)),
+      "project.search" => Ok(self.search_projects(params)),
+      "project.column.search" => Ok(self.search_columns(params)),
+      "edge.search" => Ok(self.search_edges(params)),
+      _ => Err((
+        String::from("ERR-CONDUIT-CALL"),
+        format!("Conduit method \"{}\" does not exist.", method),
+      )),
+    };
+  }
+
+  fn search_tasks(&self, params: &Value) -> Value {
+    let constraints = &params["constraints"];
+    let ids = strings_of(&constraints["ids"]);
+    let phids = strings_of(&constraints["phids"]);
+    let parent_ids = strings_of(&constraints["parentIDs"]);
+    let assigned = strings_of(&constraints["assigned"]);
+    let statuses = strings_of(&constraints["statuses"]);
+    let projects = strings_of(&constraints["projects"]);
+    let column_phids = strings_of(&constraints["columnPHIDs"]);
+    let only_open = params["queryKey"] == "open";
+
+    let tasks: Vec<&FakeTask> = self
+      .tasks
+      .iter()
+      .filter(|task| ids.is_empty() || ids.contains(&task.id.to_string()))
+      .filter(|task| phids.is_empty() || phids.contains(&task.phid()))
+      .filter(|task| {
+        parent_ids.is_empty()
+          || task
+            .parent_ids
+            .iter()
+            .any(|parent_id| parent_ids.contains(&parent_id.to_string()))
+      })
+      .filter(|task| {
+        assigned.is_empty()
+          || task
+            .owner_phid
+            .as_ref()
+            .map(|owner| assigned.contains(owner))
+            == Some(true)
+      })
+      .filter(|task| statuses.is_empty() || statuses.contains(&task.status))
+      .filter(|task| {
+        projects.is_empty()
+          || task
+            .project_phids
+            .iter()
+            .any(|phid| projects.contains(phid))
+      })
+      .filter(|task| {
+        column_phids.is_empty()
+          || task
+            .column_ids
+            .iter()
+            .any(|id| column_phids.contains(&column_phid(*id)))
+      })
+      .filter(|task| !only_open || task.status == "open")
+      .collect();
+
+    return self.page(params, tasks, |task| (task.id, task.to_json(&self.columns)));
+  }
+
+  fn search_users(&self, params: &Value) -> Value {
+    let constraints = &params["constraints"];
+    let phids = strings_of(&constraints["phids"]);
+    let usernames = strings_of(&constraints["usernames"]);
+
+    let users: Vec<&FakeUser> = self
+      .users
+      .iter()
+      .filter(|user| phids.is_empty() || phids.contains(&user_phid(user.id)))
+      .filter(|user| usernames.is_empty() || usernames.contains(&user.username))
+      .collect();
+
+    return self.page(params, users, |user| (user.id, user.to_json()));
+  }
+
+  fn search_projects(&self, params: &Value) -> Value {
+    let constraints = &params["constraints"];
+    let phids = strings_of(&constraints["phids"]);
+    let name = constraints["name"]
+      .as_str()
+      .unwrap_or_default()
+      .to_lowercase();
+
+    let projects: Vec<&FakeProject> = self
+      .projects
+      .iter()
+      .filter(|project| phids.is_empty() || phids.contains(&project_phid(project.id)))
+      .filter(|project| project.name.to_lowercase().contains(&name))
+      .collect();
+
+    return self.page(params, projects, |project| (project.id, project.to_json()));
+  }
+
+  fn search_columns(&self, params: &Value) -> Value {
+    let constraints = &params["constraints"];
+    let phids = strings_of(&constraints["phids"]);
+    let projects = strings_of(&constraints["projects"]);
+
+    let columns: Vec<&FakeColumn> = self
+      .columns
+      .iter()
+      .filter(|column| phids.is_empty() || phids.contains(&column.phid()))
+      .filter(|column| projects.is_empty() || projects.contains(&project_phid(column.project_id)))
+      .collect();
+
+    return self.page(params, columns, |column| {
+      (column.id, column.to_json(&self.projects))
+    });
+  }
+
+  fn search_edges(&self, params: &Value) -> Value {
+    let source_phids = strings_of(&params["sourcePHIDs"]);
+    let types = strings_of(&params["types"]);
+
+    let edges: Vec<Value> = self
+      .tasks
+      .iter()
+      .flat_map(|task| {
+        return task
+          .parent_ids
+          .iter()
+          .map(move |parent_id| (task_phid(*parent_id), task.phid()));
+      })
+      .filter(|(parent_phid, _)| source_phids.contains(parent_phid))
+      .filter(|_| types.is_empty() || types.contains(&String::from("task.subtask")))
+      .map(|(parent_phid, child_phid)| {
+        return json!({
+          "sourcePHID": parent_phid,
+          "edgeType": "task.subtask",
+          "destinationPHID": child_phid,
+        });
+      })
+      .collect();
+
+    return json!({
+      "data": edges,
+      "cursor": { "limit": MAX_PAGE_SIZE, "after": null, "before": null },
+    });
+  }
+
+  /// Page through the items ordered by id, the cursor is the id of the last item
+  /// in the page, that's how phabricator paginates the default order.
+  fn page<T>(&self, params: &Value, items: Vec<T>, to_json: impl Fn(&T) -> (u64, Value)) -> Value {
+    let limit = params["limit"]
+      .as_str()
+      .and_then(|limit| limit.parse().ok())
+      .unwrap_or(MAX_PAGE_SIZE)
+      .min(self.page_size);
+
+    let after: u64 = params["after"]
+      .as_str()
+      .and_then(|after| after.parse().ok())
+      .unwrap_or_default();
+
+    let mut items: Vec<(u64, Value)> = items
+      .iter()
+      .map(to_json)
+      .filter(|(id, _)| *id > after)
+      .collect();
+
+    items.sort_by_key(|(id, _)| *id);
+
+    let has_next_page = items.len() > limit;
+    let page: Vec<(u64, Value)> = items.into_iter().take(limit).collect();
+    let next_after = match page.last() {
+      Some((id, _)) if has_next_page => json!(id.to_string()),
+      _ => Value::Null,
+    };
+
+    return json!({
+      "data": page.into_iter().map(|(_, item)| item).collect::<Vec<Value>>(),
+      "maps": {},
+      "query": { "queryKey": params["queryKey"] },
+      "cursor": {
+        "limit": limit,
+        "after": next_after,
+        "before": null,
+        "order": params["order"],
+      },
+    });
+  }
+
+  fn edit_task(&mut self, params: &Value) -> Result<Value, (String, String)> {
+    let mut task = match params["objectIdentifier"].as_str() {
+      Some(identifier) => {
+        let id: Option<u64> = identifier.trim_start_matches('T').parse().ok();
+
+        self
+          .tasks
+          .iter()
+          .find(|task| Some(task.id) == id || task.phid() == identifier)
+          .cloned()
+          .ok_or_else(|| {
+            return (
+              String::from("ERR-CONDUIT-CORE"),
+              format!("Failed to load object \"{}\".", identifier),
+            );
+          })?
+      }
+      None => {
+        let id = self
+          .tasks
+          .iter()
+          .map(|task| task.id)
+          .max()
+          .unwrap_or_default()
+          + 1;
+
+        FakeTask::new(id, "")
+      }
+    };
+
+    let transactions = params["transactions"]
+      .as_array()
+      .cloned()
+      .unwrap_or_default();
+
+    for transaction in transactions.iter() {
+      self.apply_transaction(&mut task, transaction)?;
+    }
+
+    if task.title.is_empty() {
+      return Err((
+        String::from("ERR-CONDUIT-CORE"),
+        String::from("Validation errors:\n  - Tasks must have a name."),
+      ));
+    }
+
+    task.date_modified += 1;
+
+    let result = json!({
+      "object": { "id": task.id, "phid": task.phid() },
+      "transactions": transactions
+        .iter()
+        .enumerate()
+        .map(|(i, _)| json!({ "phid": format!("PHID-XACT-TASK-{}{}", task.id, i) }))
+        .collect::<Vec<Value>>(),
+    });
+
+    match self
+      .tasks
+      .iter_mut()
+      .find(|existing| existing.id == task.id)
+    {
+      Some(existing) => *existing = task,
+      None => self.tasks.push(task),
+    }
+
+    return Ok(result);
+  }
+
+  fn apply_transaction(
+    &mut self,
+    task: &mut FakeTask,
+    transaction: &Value,
+  ) -> Result<(), (String, String)> {
+    let value = &transaction["value"];
+    let string_value = value.as_str().unwrap_or_default().to_owned();
+    let task_ids_of = |phids: Vec<String>| -> Vec<u64> {
+      return phids
+        .iter()
+        .filter_map(|phid| phid.trim_start_matches("PHID-TASK-").parse().ok())
+        .collect();
+    };
+
+    match transaction["type"].as_str().unwrap_or_default() {
+      "title" => task.title = string_value,
+      "description" => task.description = string_value,
+      "status" => task.status = string_value,
+      "priority" => task.priority = string_value,
+      // Null is sent as an empty form value
+      "owner" => task.owner_phid = Some(string_value).filter(|owner| !owner.is_empty()),
+      "points" => task.points = string_value.parse().ok(),
+      "projects.add" => task.project_phids.extend(strings_of(value)),
+      "projects.remove" => {
+        let phids = strings_of(value);
+        task.project_phids.retain(|phid| !phids.contains(phid));
+      }
+      "projects.set" => task.project_phids = strings_of(value),
+      "parent" | "parents.add" => {
+        let parent_phids = match value {
+          Value::String(phid) => vec![phid.clone()],
+          _ => strings_of(value),
+        };
+
+        task.parent_ids.extend(task_ids_of(parent_phids));
+      }
+      "parents.remove" => {
+        let parent_ids = task_ids_of(strings_of(value));
+        task.parent_ids.retain(|id| !parent_ids.contains(id));
+      }
+      "subtasks.add" | "subtasks.remove" => {
+        let is_add = transaction["type"] == "subtasks.add";
+
+        for subtask_id in task_ids_of(strings_of(value)) {
+          if let Some(subtask) = self.tasks.iter_mut().find(|t| t.id == subtask_id) {
+            subtask.parent_ids.retain(|parent_id| *parent_id != task.id);
+
+            if is_add {
+              subtask.parent_ids.push(task.id);
+            }
+          }
+        }
+      }
+      "column" => {
+        for column_phid in strings_of(value) {
+          let column = self
+            .columns
+            .iter()
+            .find(|column| column.phid() == column_phid)
+            .cloned()
+            .ok_or_else(|| {
+              return (
+                String::from("ERR-CONDUIT-CORE"),
+                format!("Column \"{}\" does not exist.", column_phid),
+              );
+            })?;
+
+          // A task is in one column per board
+          let columns = &self.columns;
+
+          task.column_ids.retain(|column_id| {
+            return !columns
+              .iter()
+              .any(|c| c.id == *column_id && c.project_id == column.project_id);
+          });
+
+          *task = task.clone().in_column(&column);
+        }
+      }
+      "comment" => self.comments.push((task.id, string_value)),
+      transaction_type => {
+        return Err((
+          String::from("ERR-CONDUIT-CORE"),
+          format!(
+            "Transaction with key \"{}\" is not a valid transaction type.",
+            transaction_type
+          ),
+        ));
+      }
+    }
+
+    return Ok(());
+  }
+}
+
+/// List constraint values, conduit receives every value as a form string.
+fn strings_of(value: &Value) -> Vec<String> {
+  return value
+    .as_array()
+    .map(|values| {
+      return values
+        .iter()
+        .filter_map(|value| value.as_str().map(ToOwned::to_owned))
+        .collect();
+    })
+    .unwrap_or_default();
+}
+
+/// Rebuild the nested params from php style form fields, e.g.
+/// `constraints[ids][0]=1` becomes `{ "constraints": { "ids": ["1"] } }`.
+fn params_from_form(form: &[(String, String)]) -> Value {
+  let mut params = Value::Object(Map::new());
+
+  for (key, value) in form {
+    let path: Vec<&str> = key
+      .split('[')
+      .map(|segment| segment.trim_end_matches(']'))
+      .collect();
+
+    let mut node = &mut params;
+
+    for segment in path.iter() {
+      node = node
+        .as_object_mut()
+        .unwrap()
+        .entry(segment.to_string())
+        .or_insert_with(|| Value::Object(Map::new()));
+    }
+
+    *node = json!(value);
+  }
+
+  return objects_with_index_keys_into_arrays(params);
+}
+
+fn objects_with_index_keys_into_arrays(value: Value) -> Value {
+  let map = match value {
+    Value::Object(map) => map,
+    value => return value,
+  };
+
+  let is_array = !map.is_empty() && map.keys().all(|key| key.parse::<usize>().is_ok());
+
+  if !is_array {
+    return Value::Object(
+      map
+        .into_iter()
+        .map(|(key, value)| (key, objects_with_index_keys_into_arrays(value)))
+        .collect(),
+    );
+  }
+
+  let mut items: Vec<(usize, Value)> = map
+    .into_iter()
+    .map(|(key, value)| {
+      (
+        key.parse().unwrap(),
+        objects_with_index_keys_into_arrays(value),
+      )
+    })
+    .collect();
+
+  items.sort_by_key(|(index, _)| *index);
+
+  return Value::Array(items.into_iter().map(|(_, value)| value).collect());
+}
+
+async fn handle_request(
+  conduit: Arc<Mutex<FakeConduit>>,
+  request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+  let method = request.uri().path().trim_start_matches("/api/").to_owned();
+
+  let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+  let mut form: Vec<(String, String)> = form_urlencoded::parse(&body).into_owned().collect();
+
+  let api_token = form
+    .iter()
+    .position(|(key, _)| key == "api.token")
+    .map(|position| form.remove(position).1);
+
+  let (status, body) = if api_token.as_deref() != Some(API_TOKEN) {
+    (
+      StatusCode::OK,
+      json!({
+        "result": null,
+        "error_code": "ERR-INVALID-AUTH",
+        "error_info": "API token \"x\" has the wrong length. API tokens should be 32 characters long.",
+      }),
+    )
+  } else {
+    conduit
+      .lock()
+      .unwrap()
+      .handle(&method, params_from_form(&form))
+  };
+
+  let body = match body {
+    Value::String(text) => text,
+    body => body.to_string(),
+  };
+
+  return Ok(
+    Response::builder()
+      .status(status)
+      .header("Content-Type", "application/json")
+      .body(Body::from(body))
+      .unwrap(),
+  );
+}
+
+/// Fake conduit served on a random local port, it stops with the test runtime.
+pub struct FakeConduitServer {
+  pub addr: SocketAddr,
+  pub conduit: Arc<Mutex<FakeConduit>>,
+}
+
+impl FakeConduitServer {
+  pub fn start(conduit: FakeConduit) -> FakeConduitServer {
+    let conduit = Arc::new(Mutex::new(conduit));
+    let service_conduit = conduit.clone();
+
+    let make_service = make_service_fn(move |_| {
+      let conduit = service_conduit.clone();
+
+      return async move {
+        return Ok::<_, Infallible>(service_fn(move |request| {
+          return handle_request(conduit.clone(), request);
+        }));
+      };
+    });
+
+    let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+    let addr = server.local_addr();
+
+    tokio::spawn(server);
+
+    return FakeConduitServer { addr, conduit };
+  }
+
+  pub fn host(&self) -> String {
+    return format!("http://{}", self.addr);
+  }
+
+  /// Client config pointing to this server, retries back off quickly.
+  pub fn client_config(&self) -> PhabricatorClientConfig {
+    return PhabricatorClientConfig {
+      host: self.host(),
+      api_token: API_TOKEN.to_owned(),
+      cert_identity_config: None,
+      max_concurrency: 4,
+      retry_config: RetryConfig {
+        max_attempts: 3,
+        initial_backoff_ms: 1,
+        max_backoff_ms: 5,
+        retry_on_statuses: vec![502, 503],
+      },
+      connect_timeout_secs: 5,
+      request_timeout_secs: 5,
+      rate_limit_config: None,
+      cache_config: None,
+    };
+  }
+}
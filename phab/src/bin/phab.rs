@@ -1,36 +1,155 @@
+#![allow(clippy::needless_return)]
+
+use std::path::Path;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
 use clap::App as Cli;
 use clap::Arg;
 use clap::ArgMatches;
 use clap::SubCommand;
-use env_logger;
 
+use anyhow::anyhow;
+use lib::comments::comment_header;
+use lib::duration::parse_duration_secs;
+use lib::editor;
+use lib::graph::EdgeKind;
+use lib::graph::GraphFormat;
+use lib::graph::TaskGraph;
+use lib::kanban;
+use lib::output::OutputFormat;
 use lib::types::ResultAnyError;
+use lib::users::resolve_user_phid;
+use phab_lib::client::cassette::RecordingTransport;
+use phab_lib::client::cassette::ReplayTransport;
+use phab_lib::client::config::PhabricatorClientConfig;
+use phab_lib::client::phabricator::ErrorType;
 use phab_lib::client::phabricator::PhabricatorClient;
+use phab_lib::client::revision_query::RevisionQuery;
+use phab_lib::client::task_edit::TaskTransaction;
+use phab_lib::client::task_query::TaskQuery;
+use phab_lib::client::transport::HttpTransport;
+use phab_lib::dto::Column;
+use phab_lib::dto::Comment;
+use phab_lib::dto::Revision;
+use phab_lib::dto::Task;
 use phab_lib::dto::TaskFamily;
+use phab_lib::dto::User;
+use phab_lib::utils::fuzzy::fuzzy_match;
+use terminal_size::terminal_size;
+use terminal_size::Width;
 
 pub mod built_info {
   include!(concat!(env!("OUT_DIR"), "/built.rs"));
 }
 
 #[tokio::main]
-pub async fn main() -> ResultAnyError<()> {
+pub async fn main() {
   env_logger::init();
 
+  if let Err(err) = run().await {
+    eprintln!("Error: {}", error_message(&err));
+
+    std::process::exit(1);
+  }
+}
+
+/// Conduit errors already have readable messages, we don't need to show the error chain.
+fn error_message(err: &anyhow::Error) -> String {
+  return match err.downcast_ref::<ErrorType>() {
+    Some(ErrorType::ConduitError { code, info, method }) => {
+      log::debug!("Conduit error when calling {}", method);
+
+      if code == "ERR-INVALID-AUTH" {
+        format!("{}: {}\nPlease check api_token in ~/.phab", code, info)
+      } else {
+        format!("{}: {}", code, info)
+      }
+    }
+    _ => format!("{:?}", err),
+  };
+}
+
+async fn run() -> ResultAnyError<()> {
   let cli = Cli::new("phab")
     .version(built_info::PKG_VERSION)
     .author(built_info::PKG_AUTHORS)
     .setting(clap::AppSettings::ArgRequiredElseHelp)
     .about(built_info::PKG_DESCRIPTION)
+    .arg(
+      Arg::with_name("offline")
+        .long("offline")
+        .global(true)
+        .help("Only serve responses from cache, nothing is sent to phabricator"),
+    )
     .subcommand(task_cmd())
+    .subcommand(diff_cmd())
+    .subcommand(board_cmd())
     .get_matches();
 
   if let Some(task_cli) = cli.subcommand_matches("task") {
     handle_task_cli(task_cli).await?;
   }
 
+  if let Some(diff_cli) = cli.subcommand_matches("diff") {
+    handle_diff_cli(diff_cli).await?;
+  }
+
+  if let Some(board_cli) = cli.subcommand_matches("board") {
+    handle_board_cli(board_cli).await?;
+  }
+
   return Ok(());
 }
 
+fn load_config(cli: &ArgMatches<'_>) -> ResultAnyError<PhabricatorClientConfig> {
+  let home_dir = std::env::var("HOME").unwrap();
+  let mut config = lib::config::parse_from_setting_path(format!("{}/.phab", home_dir))?;
+
+  // Responses are only cached when caching is configured, there's nothing to serve otherwise
+  if cli.is_present("offline") {
+    let cache_config = config.cache_config.as_mut().ok_or_else(|| {
+      return ErrorType::ValidationError {
+        message: format!(
+          "--offline needs cache_config to be configured in {}/.phab",
+          home_dir
+        ),
+      };
+    })?;
+
+    cache_config.offline = true;
+  }
+
+  if let Some(cache_config) = config.cache_config.as_mut() {
+    cache_config
+      .path
+      .get_or_insert_with(|| format!("{}/.phab_cache.json", home_dir));
+  }
+
+  return Ok(config);
+}
+
+/// `PHAB_RECORD=path` records every conduit call into a cassette file,
+/// `PHAB_REPLAY=path` answers the calls from a recorded cassette without a server.
+fn new_client(config: PhabricatorClientConfig) -> ResultAnyError<PhabricatorClient> {
+  if let Ok(path) = std::env::var("PHAB_REPLAY") {
+    let transport = ReplayTransport::from_file(Path::new(&path))?;
+
+    return Ok(
+      PhabricatorClient::with_transport(transport).with_max_concurrency(config.max_concurrency),
+    );
+  }
+
+  if let Ok(path) = std::env::var("PHAB_RECORD") {
+    let max_concurrency = config.max_concurrency;
+    let transport = RecordingTransport::new(HttpTransport::new(config)?, path);
+
+    return Ok(PhabricatorClient::with_transport(transport).with_max_concurrency(max_concurrency));
+  }
+
+  return PhabricatorClient::new(config);
+}
+
 fn task_cmd<'a, 'b>() -> Cli<'a, 'b> {
   let task_id_arg = Arg::with_name("task_id")
     .takes_value(true)
@@ -48,31 +167,631 @@ fn task_cmd<'a, 'b>() -> Cli<'a, 'b> {
     .subcommand(
       SubCommand::with_name("detail")
         .about("View task detail")
-        .arg(task_id_arg)
+        .arg(&task_id_arg)
+        .arg(
+          Arg::with_name("max_depth")
+            .long("max-depth")
+            .takes_value(true)
+            .help("Maximum number of subtask levels to show, e.g. 1 only shows direct subtasks"),
+        )
+        .arg(
+          Arg::with_name("interactive")
+            .long("interactive")
+            .short("i")
+            .takes_value(false)
+            .conflicts_with("print_json")
+            .conflicts_with("output")
+            .help("Browse the task tree in a full screen terminal ui"),
+        )
+        .arg(
+          Arg::with_name("output")
+            .long("output")
+            .short("o")
+            .takes_value(true)
+            .possible_values(&OutputFormat::NAMES)
+            .conflicts_with("print_json")
+            .help("Output format, defaults to the indented task list"),
+        )
+        .arg(&print_json),
+    )
+    .subcommand(
+      SubCommand::with_name("create")
+        .about("Create a new task")
+        .arg(
+          Arg::with_name("title")
+            .long("title")
+            .takes_value(true)
+            .required(true)
+            .help("Task title"),
+        )
+        .args(&task_edit_args())
+        .arg(
+          Arg::with_name("parent")
+            .long("parent")
+            .takes_value(true)
+            .help("Parent task id, e.g. T123"),
+        )
+        .arg(&print_json),
+    )
+    .subcommand(
+      SubCommand::with_name("edit")
+        .about("Edit an existing task")
+        .arg(&task_id_arg)
+        .arg(
+          Arg::with_name("title")
+            .long("title")
+            .takes_value(true)
+            .help("Task title"),
+        )
+        .args(&task_edit_args())
+        .arg(
+          Arg::with_name("parent")
+            .long("parent")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Add parent task id, e.g. T123"),
+        )
+        .arg(&print_json),
+    )
+    .subcommand(
+      SubCommand::with_name("list")
+        .alias("search")
+        .about("List tasks matching the given filters")
+        .arg(
+          Arg::with_name("assigned")
+            .long("assigned")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Assignee username, use `me` for yourself"),
+        )
+        .arg(
+          Arg::with_name("status")
+            .long("status")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Status keyword, e.g. open, resolved"),
+        )
+        .arg(
+          Arg::with_name("project")
+            .long("project")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Project name"),
+        )
+        .arg(
+          Arg::with_name("column")
+            .long("column")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .requires("project")
+            .help(
+              "Workboard column name of the given --project, partial names are matched as well",
+            ),
+        )
+        .arg(
+          Arg::with_name("updated_since")
+            .long("updated-since")
+            .takes_value(true)
+            .help("Only tasks updated within the given duration, e.g. 12h, 7d, 2w"),
+        )
+        .arg(
+          Arg::with_name("query")
+            .long("query")
+            .takes_value(true)
+            .help("Full-text search query"),
+        )
+        .arg(
+          Arg::with_name("query_key")
+            .long("query-key")
+            .takes_value(true)
+            .help("Builtin or saved query key, e.g. assigned, authored, subscribed"),
+        )
+        .arg(
+          Arg::with_name("order")
+            .long("order")
+            .takes_value(true)
+            .help("Result order, e.g. priority, updated, newest, oldest"),
+        )
+        .arg(
+          Arg::with_name("limit")
+            .long("limit")
+            .takes_value(true)
+            .help("Maximum number of tasks to show"),
+        )
+        .arg(&print_json),
+    )
+    .subcommand(
+      SubCommand::with_name("comment")
+        .about("View task comments, or add a new one with --message")
+        .arg(&task_id_arg)
+        .arg(
+          Arg::with_name("message")
+            .long("message")
+            .short("m")
+            .takes_value(true)
+            .help("Comment to add"),
+        )
+        .arg(&print_json),
+    )
+    .subcommand(
+      SubCommand::with_name("graph")
+        .about("Export task tree as a graphviz or mermaid diagram")
+        .arg(&task_id_arg)
+        .arg(
+          Arg::with_name("format")
+            .long("format")
+            .takes_value(true)
+            .possible_values(&GraphFormat::NAMES)
+            .default_value("mermaid")
+            .help("Diagram format"),
+        )
+        .arg(
+          Arg::with_name("edges")
+            .long("edges")
+            .takes_value(true)
+            .multiple(true)
+            .use_delimiter(true)
+            .possible_values(&EdgeKind::OPTIONAL_NAMES)
+            .help("Also draw blocked tasks outside of the tree and related (mentioned) tasks"),
+        )
+        .arg(
+          Arg::with_name("max_depth")
+            .long("max-depth")
+            .takes_value(true)
+            .help("Maximum number of subtask levels to draw"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("move")
+        .about("Move task to another workboard column")
+        .arg(&task_id_arg)
+        .arg(
+          Arg::with_name("column")
+            .long("column")
+            .takes_value(true)
+            .required(true)
+            .help("Column name, partial names such as `review` are matched as well"),
+        )
+        .arg(
+          Arg::with_name("board")
+            .long("board")
+            .takes_value(true)
+            .help("Project name of the board, required if the column name matches several boards"),
+        )
         .arg(&print_json),
     );
 }
 
+/// Args that are shared by `task create` and `task edit`.
+fn task_edit_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+  return vec![
+    Arg::with_name("description")
+      .long("description")
+      .takes_value(true)
+      .conflicts_with_all(&["description_file", "editor"])
+      .help("Task description"),
+    Arg::with_name("description_file")
+      .long("description-file")
+      .takes_value(true)
+      .conflicts_with("editor")
+      .help("Read task description from the given file"),
+    Arg::with_name("editor")
+      .long("editor")
+      .short("e")
+      .takes_value(false)
+      .help("Write task description using $EDITOR"),
+    Arg::with_name("status")
+      .long("status")
+      .takes_value(true)
+      .help("Status keyword, e.g. open, resolved, wontfix"),
+    Arg::with_name("priority")
+      .long("priority")
+      .takes_value(true)
+      .help("Priority keyword, e.g. unbreak, high, normal, low, wish"),
+    Arg::with_name("points")
+      .long("points")
+      .takes_value(true)
+      .help("Task points"),
+    Arg::with_name("assign")
+      .long("assign")
+      .takes_value(true)
+      .help("Username to assign, use `me` to assign yourself or `none` to unassign"),
+    Arg::with_name("project")
+      .long("project")
+      .takes_value(true)
+      .multiple(true)
+      .number_of_values(1)
+      .help("Tag the task with the given project name"),
+  ];
+}
+
+fn diff_cmd<'a, 'b>() -> Cli<'a, 'b> {
+  let print_json = Arg::with_name("print_json")
+    .takes_value(false)
+    .long("print-json")
+    .help("Set if you want to print json");
+
+  return SubCommand::with_name("diff")
+    .setting(clap::AppSettings::ArgRequiredElseHelp)
+    .about("differential revision cli")
+    .subcommand(
+      SubCommand::with_name("list")
+        .about("List revisions matching the given filters")
+        .arg(
+          Arg::with_name("needs_review")
+            .long("needs-review")
+            .takes_value(false)
+            .help("Only revisions that are waiting for your review"),
+        )
+        .arg(
+          Arg::with_name("author")
+            .long("author")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Author username, use `me` for yourself"),
+        )
+        .arg(
+          Arg::with_name("reviewer")
+            .long("reviewer")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Reviewer username, use `me` for yourself"),
+        )
+        .arg(
+          Arg::with_name("status")
+            .long("status")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Status keyword, e.g. needs-review, needs-revision, accepted, published"),
+        )
+        .arg(
+          Arg::with_name("limit")
+            .long("limit")
+            .takes_value(true)
+            .help("Maximum number of revisions to show"),
+        )
+        .arg(&print_json),
+    )
+    .subcommand(
+      SubCommand::with_name("detail")
+        .about("View revision detail")
+        .arg(
+          Arg::with_name("revision_id")
+            .takes_value(true)
+            .required(true)
+            .help("revision id, e.g. D123"),
+        )
+        .arg(&print_json),
+    );
+}
+
+fn board_cmd<'a, 'b>() -> Cli<'a, 'b> {
+  return SubCommand::with_name("board")
+    .setting(clap::AppSettings::ArgRequiredElseHelp)
+    .about("workboard cli")
+    .subcommand(
+      SubCommand::with_name("show")
+        .about("Show project workboard as kanban, one lane per column")
+        .arg(
+          Arg::with_name("project_name")
+            .takes_value(true)
+            .required(true)
+            .help("project name, e.g. \"Backend\""),
+        )
+        .arg(
+          Arg::with_name("all")
+            .long("all")
+            .takes_value(false)
+            .help("Include closed tasks"),
+        )
+        .arg(
+          Arg::with_name("print_json")
+            .takes_value(false)
+            .long("print-json")
+            .help("Set if you want to print json"),
+        ),
+    );
+}
+
+async fn handle_diff_cli(cli: &ArgMatches<'_>) -> ResultAnyError<()> {
+  let config = load_config(cli)?;
+  let phabricator = new_client(config)?;
+
+  if let Some(diff_list_cli) = cli.subcommand_matches("list") {
+    return handle_diff_list_cli(&phabricator, diff_list_cli).await;
+  }
+
+  if let Some(diff_detail_cli) = cli.subcommand_matches("detail") {
+    return handle_diff_detail_cli(&phabricator, diff_detail_cli).await;
+  }
+
+  return Ok(());
+}
+
+async fn handle_diff_list_cli(
+  phabricator: &PhabricatorClient,
+  cli: &ArgMatches<'_>,
+) -> ResultAnyError<()> {
+  let mut query = RevisionQuery::new().order("updated");
+  let mut reviewer_phids = vec![];
+  let statuses: Vec<&str> = cli
+    .values_of("status")
+    .map(Iterator::collect)
+    .unwrap_or_default();
+
+  if let Some(usernames) = cli.values_of("reviewer") {
+    for username in usernames {
+      reviewer_phids.push(resolve_user_phid(phabricator, username).await?);
+    }
+  }
+
+  if let Some(usernames) = cli.values_of("author") {
+    let mut author_phids = vec![];
+
+    for username in usernames {
+      author_phids.push(resolve_user_phid(phabricator, username).await?);
+    }
+
+    query = query.author_phids(author_phids.iter().map(String::as_str).collect());
+  }
+
+  if let Some(limit) = parse_usize_arg(cli, "limit")? {
+    query = query.limit(limit);
+  }
+
+  query = query
+    .reviewer_phids(reviewer_phids.iter().map(String::as_str).collect())
+    .statuses(statuses);
+
+  if cli.is_present("needs_review") {
+    query = query.needs_review_by(&phabricator.get_current_user().await?.phid);
+  }
+
+  let revisions = phabricator.search_revisions(&query).await?;
+
+  if cli.is_present("print_json") {
+    println!("{}", serde_json::to_string(&revisions)?);
+  } else {
+    for revision in revisions.iter() {
+      print_revision(revision);
+    }
+  }
+
+  return Ok(());
+}
+
+async fn handle_diff_detail_cli(
+  phabricator: &PhabricatorClient,
+  cli: &ArgMatches<'_>,
+) -> ResultAnyError<()> {
+  let revision_id = cli.value_of("revision_id").unwrap();
+  let revision = phabricator
+    .get_revision_by_id(revision_id)
+    .await?
+    .ok_or_else(|| anyhow!("Could not find revision {}", revision_id))?;
+
+  if cli.is_present("print_json") {
+    println!("{}", serde_json::to_string(&revision)?);
+
+    return Ok(());
+  }
+
+  let mut user_phids: Vec<&str> = revision
+    .reviewers
+    .iter()
+    .map(|reviewer| reviewer.reviewer_phid.as_str())
+    .collect();
+
+  user_phids.push(revision.author_phid.as_str());
+
+  let users = phabricator.get_users_by_phids(user_phids).await?;
+  let display_name = |phid: &str| -> String {
+    return users
+      .iter()
+      .find(|user| user.phid == phid)
+      .map(User::mention)
+      .unwrap_or_else(|| phid.to_owned());
+  };
+
+  print_revision(&revision);
+  println!("Author: {}", display_name(&revision.author_phid));
+
+  if let Some(repository_phid) = &revision.repository_phid {
+    println!("Repository: {}", repository_phid);
+  }
+
+  println!("Reviewers:");
+
+  for reviewer in revision.reviewers.iter() {
+    println!(
+      "  {} ({}{})",
+      display_name(&reviewer.reviewer_phid),
+      reviewer.status,
+      if reviewer.is_blocking {
+        ", blocking"
+      } else {
+        ""
+      }
+    );
+  }
+
+  if !revision.summary.is_empty() {
+    println!("Summary:");
+
+    for line in revision.summary.lines() {
+      println!("  {}", line);
+    }
+  }
+
+  println!("Test plan:");
+
+  for line in revision.test_plan.lines() {
+    println!("  {}", line);
+  }
+
+  return Ok(());
+}
+
+async fn handle_board_cli(cli: &ArgMatches<'_>) -> ResultAnyError<()> {
+  let config = load_config(cli)?;
+  let phabricator = new_client(config)?;
+
+  if let Some(board_show_cli) = cli.subcommand_matches("show") {
+    return handle_board_show_cli(&phabricator, board_show_cli).await;
+  }
+
+  return Ok(());
+}
+
+async fn handle_board_show_cli(
+  phabricator: &PhabricatorClient,
+  cli: &ArgMatches<'_>,
+) -> ResultAnyError<()> {
+  let project_name = cli.value_of("project_name").unwrap();
+  let project_phid = resolve_project_phid(phabricator, project_name).await?;
+  let workboard = phabricator
+    .get_workboard(&project_phid, cli.is_present("all"))
+    .await?;
+
+  if cli.is_present("print_json") {
+    println!("{}", serde_json::to_string(&workboard)?);
+
+    return Ok(());
+  }
+
+  if workboard.lanes.is_empty() {
+    println!("{} has no workboard columns", workboard.project.name);
+
+    return Ok(());
+  }
+
+  for line in kanban::render_lanes(&kanban::lanes_of(&workboard), terminal_width()) {
+    println!("{}", line.trim_end());
+  }
+
+  return Ok(());
+}
+
+/// Parse an optional numeric arg, the error names the arg e.g. `Invalid max depth x`.
+fn parse_usize_arg(cli: &ArgMatches<'_>, name: &str) -> ResultAnyError<Option<usize>> {
+  let label = name.replace('_', " ");
+
+  return cli
+    .value_of(name)
+    .map(|value| {
+      return value
+        .parse()
+        .map_err(|_| anyhow!("Invalid {} {}, {} must be a number", label, value, label));
+    })
+    .transpose();
+}
+
+/// Fall back to `COLUMNS` then 80 columns when stdout is not a terminal, e.g. piped.
+fn terminal_width() -> usize {
+  if let Some((Width(width), _)) = terminal_size() {
+    return width as usize;
+  }
+
+  return std::env::var("COLUMNS")
+    .ok()
+    .and_then(|columns| columns.parse().ok())
+    .unwrap_or(80);
+}
+
 async fn handle_task_cli(cli: &ArgMatches<'_>) -> ResultAnyError<()> {
-  let home_dir = std::env::var("HOME").unwrap();
-  let config = lib::config::parse_from_setting_path(format!("{}/.phab", home_dir))?;
+  let config = load_config(cli)?;
+
+  if let Some(task_create_cli) = cli.subcommand_matches("create") {
+    let phabricator = new_client(config)?;
+
+    return handle_task_create_cli(&phabricator, task_create_cli).await;
+  }
+
+  if let Some(task_edit_cli) = cli.subcommand_matches("edit") {
+    let phabricator = new_client(config)?;
+
+    return handle_task_edit_cli(&phabricator, task_edit_cli).await;
+  }
+
+  if let Some(task_list_cli) = cli.subcommand_matches("list") {
+    let phabricator = new_client(config)?;
+
+    return handle_task_list_cli(&phabricator, task_list_cli).await;
+  }
+
+  if let Some(task_graph_cli) = cli.subcommand_matches("graph") {
+    let phabricator = new_client(config)?;
+
+    return handle_task_graph_cli(&phabricator, task_graph_cli).await;
+  }
+
+  if let Some(task_move_cli) = cli.subcommand_matches("move") {
+    let phabricator = new_client(config)?;
+
+    return handle_task_move_cli(&phabricator, task_move_cli).await;
+  }
+
+  if let Some(task_comment_cli) = cli.subcommand_matches("comment") {
+    let phabricator = new_client(config)?;
+
+    return handle_task_comment_cli(&phabricator, task_comment_cli).await;
+  }
 
   if let Some(task_detail_cli) = cli.subcommand_matches("detail") {
     let parent_task_id = task_detail_cli.value_of("task_id").unwrap();
     let print_json = task_detail_cli.is_present("print_json");
+    let max_depth = parse_usize_arg(task_detail_cli, "max_depth")?;
+
+    let output_format: Option<OutputFormat> = task_detail_cli
+      .value_of("output")
+      .map(str::parse)
+      .transpose()?;
 
-    let phabricator = PhabricatorClient::new(config)?;
+    let host = config.host.clone();
+    let phabricator = new_client(config)?;
 
-    let task_family = phabricator.get_task_family(parent_task_id).await?;
+    let task_family = phabricator
+      .get_task_family(parent_task_id, max_depth)
+      .await?;
 
     if task_family.is_none() {
       println!("Could not find task {}", parent_task_id);
+
+      return Ok(());
+    }
+
+    let mut task_family = task_family.unwrap();
+
+    phabricator
+      .fetch_task_family_related_objects(&mut task_family)
+      .await?;
+
+    phabricator
+      .fetch_task_family_users(&mut task_family)
+      .await?;
+
+    phabricator
+      .fetch_task_family_projects(&mut task_family)
+      .await?;
+
+    if task_detail_cli.is_present("interactive") {
+      return lib::tui::run(&phabricator, task_family).await;
     }
 
     // Just for printing purposes
-    let task_families = vec![task_family.unwrap()];
+    let task_families = vec![task_family];
 
-    if print_json {
+    if let Some(output_format) = output_format {
+      print!("{}", output_format.formatter(&host).format(&task_families)?);
+    } else if print_json {
       println!("{}", TaskFamily::json_string(&task_families)?);
     } else {
       print_tasks(&task_families, 0);
@@ -82,36 +801,451 @@ async fn handle_task_cli(cli: &ArgMatches<'_>) -> ResultAnyError<()> {
   return Ok(());
 }
 
-fn print_tasks(task_families: &[TaskFamily], indentation_level: usize) {
-  let indentation = std::iter::repeat(" ")
-    .take(indentation_level * 2)
-    .collect::<String>();
+async fn handle_task_create_cli(
+  phabricator: &PhabricatorClient,
+  cli: &ArgMatches<'_>,
+) -> ResultAnyError<()> {
+  let mut transactions = vec![TaskTransaction::Title(
+    cli.value_of("title").unwrap().to_owned(),
+  )];
+
+  if let Some(parent_task_id) = cli.value_of("parent") {
+    let parent_task = fetch_task(phabricator, parent_task_id).await?;
+
+    transactions.push(TaskTransaction::Parent(parent_task.phid));
+  }
+
+  transactions.extend(task_edit_transactions(phabricator, cli, "").await?);
+
+  let task = phabricator.create_task(transactions).await?;
+
+  print_task_result(&task, cli.is_present("print_json"))?;
+
+  return Ok(());
+}
+
+async fn handle_task_edit_cli(
+  phabricator: &PhabricatorClient,
+  cli: &ArgMatches<'_>,
+) -> ResultAnyError<()> {
+  let task_id = cli.value_of("task_id").unwrap();
+  let task = fetch_task(phabricator, task_id).await?;
+  let mut transactions = vec![];
+
+  if let Some(title) = cli.value_of("title") {
+    transactions.push(TaskTransaction::Title(title.to_owned()));
+  }
+
+  if let Some(parent_task_ids) = cli.values_of("parent") {
+    let mut parent_phids = vec![];
+
+    for parent_task_id in parent_task_ids {
+      parent_phids.push(fetch_task(phabricator, parent_task_id).await?.phid);
+    }
+
+    transactions.push(TaskTransaction::AddParents(parent_phids));
+  }
+
+  transactions.extend(task_edit_transactions(phabricator, cli, &task.description).await?);
+
+  if transactions.is_empty() {
+    return Err(anyhow!("Nothing to edit, see `phab task edit --help`"));
+  }
+
+  let task = phabricator.edit_task(task_id, transactions).await?;
+
+  print_task_result(&task, cli.is_present("print_json"))?;
+
+  return Ok(());
+}
+
+async fn handle_task_graph_cli(
+  phabricator: &PhabricatorClient,
+  cli: &ArgMatches<'_>,
+) -> ResultAnyError<()> {
+  let task_id = cli.value_of("task_id").unwrap();
+  let format: GraphFormat = cli.value_of("format").unwrap().parse()?;
+  let edge_kinds: Vec<EdgeKind> = cli
+    .values_of("edges")
+    .map(|names| names.map(str::parse).collect::<ResultAnyError<_>>())
+    .transpose()?
+    .unwrap_or_default();
+
+  let max_depth = parse_usize_arg(cli, "max_depth")?;
+
+  let mut task_family = phabricator
+    .get_task_family(task_id, max_depth)
+    .await?
+    .ok_or_else(|| anyhow!("Could not find task {}", task_id))?;
+
+  phabricator
+    .fetch_task_family_users(&mut task_family)
+    .await?;
+
+  let mut graph = TaskGraph::from_task_family(&task_family);
+
+  for edge_kind in edge_kinds {
+    graph.fetch_edges(phabricator, edge_kind).await?;
+  }
+
+  print!("{}", graph.format(format));
+
+  return Ok(());
+}
+
+async fn handle_task_move_cli(
+  phabricator: &PhabricatorClient,
+  cli: &ArgMatches<'_>,
+) -> ResultAnyError<()> {
+  let mut task = phabricator
+    .move_task_to_column(
+      cli.value_of("task_id").unwrap(),
+      cli.value_of("column").unwrap(),
+      cli.value_of("board"),
+    )
+    .await?;
+
+  phabricator.fetch_tasks_projects(vec![&mut task]).await?;
+
+  print_task_result(&task, cli.is_present("print_json"))?;
+
+  return Ok(());
+}
+
+async fn handle_task_list_cli(
+  phabricator: &PhabricatorClient,
+  cli: &ArgMatches<'_>,
+) -> ResultAnyError<()> {
+  let mut query = TaskQuery::new();
+
+  if let Some(usernames) = cli.values_of("assigned") {
+    let mut user_phids = vec![];
+
+    for username in usernames {
+      user_phids.push(resolve_user_phid(phabricator, username).await?);
+    }
+
+    query = query.assigned(user_phids.iter().map(String::as_str).collect());
+  }
+
+  if let Some(statuses) = cli.values_of("status") {
+    query = query.statuses(statuses.collect());
+  }
+
+  if let Some(project_names) = cli.values_of("project") {
+    let mut project_phids = vec![];
+
+    for project_name in project_names {
+      project_phids.push(resolve_project_phid(phabricator, project_name).await?);
+    }
+
+    if let Some(column_names) = cli.values_of("column") {
+      let columns = phabricator
+        .get_project_columns(project_phids.iter().map(String::as_str).collect())
+        .await?;
+
+      let column_names_of = |columns: &[&Column]| -> String {
+        return columns
+          .iter()
+          .map(|column| column.name.as_str())
+          .collect::<Vec<&str>>()
+          .join(", ");
+      };
+
+      let mut column_phids = vec![];
+
+      // Same matching as `phab task move`
+      for column_name in column_names {
+        let column = match fuzzy_match(&columns, column_name, |column| &column.name).as_slice() {
+          [column] => *column,
+          [] => {
+            return Err(anyhow!(
+              "Could not find column {}, valid columns: {}",
+              column_name,
+              column_names_of(&columns.iter().collect::<Vec<&Column>>())
+            ))
+          }
+          matches => {
+            return Err(anyhow!(
+              "Column name {} is ambiguous, matching columns: {}",
+              column_name,
+              column_names_of(matches)
+            ))
+          }
+        };
+
+        column_phids.push(column.phid.as_str());
+      }
+
+      query = query.column_phids(column_phids);
+    }
+
+    query = query.projects(project_phids.iter().map(String::as_str).collect());
+  }
+
+  if let Some(updated_since) = cli.value_of("updated_since") {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let updated_since = parse_duration_secs(updated_since)?;
+
+    query = query.modified_start(now.saturating_sub(updated_since));
+  }
+
+  if let Some(text_query) = cli.value_of("query") {
+    query = query.query(text_query);
+  }
+
+  if let Some(query_key) = cli.value_of("query_key") {
+    query = query.query_key(query_key);
+  }
+
+  if let Some(order) = cli.value_of("order") {
+    query = query.order(order);
+  }
+
+  if let Some(limit) = parse_usize_arg(cli, "limit")? {
+    query = query.limit(limit);
+  }
+
+  let mut tasks = phabricator.search_tasks(&query).await?;
 
+  phabricator
+    .fetch_tasks_users(tasks.iter_mut().collect())
+    .await?;
+
+  phabricator
+    .fetch_tasks_projects(tasks.iter_mut().collect())
+    .await?;
+
+  if cli.is_present("print_json") {
+    println!("{}", serde_json::to_string(&tasks)?);
+  } else {
+    for task in tasks.iter() {
+      print_task(task, 0);
+    }
+  }
+
+  return Ok(());
+}
+
+async fn handle_task_comment_cli(
+  phabricator: &PhabricatorClient,
+  cli: &ArgMatches<'_>,
+) -> ResultAnyError<()> {
+  let task_id = cli.value_of("task_id").unwrap();
+
+  if let Some(message) = cli.value_of("message") {
+    phabricator.add_task_comment(task_id, message).await?;
+  }
+
+  let comments = phabricator.get_task_comments(task_id).await?;
+
+  if cli.is_present("print_json") {
+    println!("{}", serde_json::to_string(&comments)?);
+  } else {
+    print_comments(&comments);
+  }
+
+  return Ok(());
+}
+
+/// Build transactions from args defined in `task_edit_args`.
+async fn task_edit_transactions(
+  phabricator: &PhabricatorClient,
+  cli: &ArgMatches<'_>,
+  current_description: &str,
+) -> ResultAnyError<Vec<TaskTransaction>> {
+  let mut transactions = vec![];
+
+  if let Some(description) = cli.value_of("description") {
+    transactions.push(TaskTransaction::Description(description.to_owned()));
+  } else if let Some(description_file) = cli.value_of("description_file") {
+    let description = std::fs::read_to_string(description_file)?;
+
+    transactions.push(TaskTransaction::Description(description));
+  } else if cli.is_present("editor") {
+    let description = editor::edit(current_description)?;
+
+    transactions.push(TaskTransaction::Description(description));
+  }
+
+  if let Some(status) = cli.value_of("status") {
+    transactions.push(TaskTransaction::Status(status.to_owned()));
+  }
+
+  if let Some(priority) = cli.value_of("priority") {
+    transactions.push(TaskTransaction::Priority(priority.to_owned()));
+  }
+
+  if let Some(points) = cli.value_of("points") {
+    let points: f64 = points
+      .parse()
+      .map_err(|_| anyhow!("Invalid points {}, points must be a number", points))?;
+
+    transactions.push(TaskTransaction::Points(Some(points)));
+  }
+
+  if let Some(username) = cli.value_of("assign") {
+    let owner_phid = match username {
+      "none" => None,
+      username => Some(resolve_user_phid(phabricator, username).await?),
+    };
+
+    transactions.push(TaskTransaction::Owner(owner_phid));
+  }
+
+  if let Some(project_names) = cli.values_of("project") {
+    let mut project_phids = vec![];
+
+    for project_name in project_names {
+      project_phids.push(resolve_project_phid(phabricator, project_name).await?);
+    }
+
+    transactions.push(TaskTransaction::AddProjects(project_phids));
+  }
+
+  return Ok(transactions);
+}
+
+async fn fetch_task(phabricator: &PhabricatorClient, task_id: &str) -> ResultAnyError<Task> {
+  return phabricator
+    .get_task_by_id(task_id)
+    .await?
+    .ok_or_else(|| anyhow!("Could not find task {}", task_id));
+}
+
+/// Resolve a project name into the project phid, an exact case insensitive match wins,
+/// otherwise the name must match only one project.
+async fn resolve_project_phid(
+  phabricator: &PhabricatorClient,
+  project_name: &str,
+) -> ResultAnyError<String> {
+  let projects = phabricator.search_projects_by_name(project_name).await?;
+
+  let exact_match = projects
+    .iter()
+    .find(|project| project.name.eq_ignore_ascii_case(project_name));
+
+  if let Some(project) = exact_match {
+    return Ok(project.phid.clone());
+  }
+
+  return match projects.as_slice() {
+    [] => Err(anyhow!("Could not find project {}", project_name)),
+    [project] => Ok(project.phid.clone()),
+    projects => Err(anyhow!(
+      "Project name {} is ambiguous, matching projects: {}",
+      project_name,
+      projects
+        .iter()
+        .map(|project| project.name.as_str())
+        .collect::<Vec<&str>>()
+        .join(", ")
+    )),
+  };
+}
+
+fn print_task_result(task: &Task, print_json: bool) -> ResultAnyError<()> {
+  if print_json {
+    println!("{}", serde_json::to_string(task)?);
+  } else {
+    print_task(task, 0);
+  }
+
+  return Ok(());
+}
+
+fn print_comments(comments: &[Comment]) {
+  for (i, comment) in comments.iter().enumerate() {
+    if i > 0 {
+      println!();
+    }
+
+    println!("{}", comment_header(comment));
+
+    for line in comment.content.lines() {
+      println!("  {}", line);
+    }
+  }
+}
+
+fn print_revision(revision: &Revision) {
+  println!(
+    "[D{} {}] {}",
+    revision.id, revision.status_name, revision.title
+  );
+}
+
+fn print_tasks(task_families: &[TaskFamily], indentation_level: usize) {
   let task_families = task_families
     .iter()
     .filter(|task_family| task_family.parent_task.status != "invalid")
     .collect::<Vec<&TaskFamily>>();
 
   for task_family in task_families {
-    let task = &task_family.parent_task;
+    print_task(&task_family.parent_task, indentation_level);
+    print_related_objects(task_family, indentation_level + 1);
+    print_tasks(&task_family.children, indentation_level + 1);
+    print_referenced_children(task_family, indentation_level + 1);
+  }
+}
 
-    let board_name = task
-      .board
-      .as_ref()
-      .map(|b| b.name.clone())
-      .or(Some(String::from("NoBoard")))
-      .unwrap();
+fn print_referenced_children(task_family: &TaskFamily, indentation_level: usize) {
+  let indentation = " ".repeat(indentation_level * 2);
 
+  for task_id in task_family.referenced_children.iter() {
+    println!("{}-> T{} (listed above)", indentation, task_id);
+  }
+}
+
+fn print_related_objects(task_family: &TaskFamily, indentation_level: usize) {
+  let indentation = " ".repeat(indentation_level * 2);
+
+  for revision in task_family.revisions.iter() {
     println!(
-      "{}[T{} {} - {} point: {}] {}",
-      indentation,
-      task.id,
-      task.status,
-      board_name,
-      task.point.or(Some(0)).unwrap(),
-      task.name,
+      "{}D{} ({}) {}",
+      indentation, revision.id, revision.status_name, revision.title
     );
+  }
 
-    print_tasks(&task_family.children, indentation_level + 1);
+  for commit in task_family.commits.iter() {
+    let short_identifier: String = commit.identifier.chars().take(12).collect();
+
+    println!("{}{} {}", indentation, short_identifier, commit.summary());
   }
 }
+
+fn print_task(task: &Task, indentation_level: usize) {
+  let indentation = " ".repeat(indentation_level * 2);
+
+  let board_names = task.board_labels();
+
+  let board_name = if board_names.is_empty() {
+    String::from("NoBoard")
+  } else {
+    board_names.join(", ")
+  };
+
+  let project_tags: String = task
+    .projects
+    .iter()
+    .map(|project| format!(" #{}", project.slug.as_ref().unwrap_or(&project.name)))
+    .collect();
+
+  let assigned = task
+    .assigned
+    .as_ref()
+    .map(|user| format!(" {}", user.mention()))
+    .unwrap_or_default();
+
+  println!(
+    "{}[T{} {} - {} point: {}] {}{}{}",
+    indentation,
+    task.id,
+    task.status,
+    board_name,
+    task.point.unwrap_or(0.0),
+    task.name,
+    assigned,
+    project_tags,
+  );
+}
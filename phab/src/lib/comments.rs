@@ -0,0 +1,22 @@
+use chrono::Local;
+use chrono::TimeZone;
+use phab_lib::dto::Comment;
+use phab_lib::dto::User;
+
+/// Comment author and local creation time, e.g. `@alice (2020-09-13 19:26):`.
+/// The author phid is shown when the author is not resolved.
+pub fn comment_header(comment: &Comment) -> String {
+  let author = comment
+    .author
+    .as_ref()
+    .map(User::mention)
+    .unwrap_or_else(|| comment.author_phid.clone());
+
+  let created_at = Local
+    .timestamp_opt(comment.created_at as i64, 0)
+    .single()
+    .map(|date| date.format("%Y-%m-%d %H:%M").to_string())
+    .unwrap_or_default();
+
+  return format!("{} ({}):", author, created_at);
+}
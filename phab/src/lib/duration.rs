@@ -0,0 +1,40 @@
+use anyhow::anyhow;
+
+use crate::types::ResultAnyError;
+
+/// Parse short duration such as `30m`, `12h`, `7d` or `2w` into seconds.
+/// ```
+/// use lib::duration::parse_duration_secs;
+///
+/// assert_eq!(parse_duration_secs("30m").unwrap(), 30 * 60);
+/// assert_eq!(parse_duration_secs("7d").unwrap(), 7 * 24 * 60 * 60);
+/// assert!(parse_duration_secs("7").is_err());
+/// assert!(parse_duration_secs("xd").is_err());
+/// assert!(parse_duration_secs("99999999999999999w").is_err());
+/// ```
+pub fn parse_duration_secs(duration: &str) -> ResultAnyError<u64> {
+  let duration = duration.trim();
+  let invalid_duration = || {
+    return anyhow!(
+      "Invalid duration {}, expected format like 30m, 12h, 7d or 2w",
+      duration
+    );
+  };
+
+  let unit = duration.chars().last().ok_or_else(invalid_duration)?;
+  let amount: u64 = duration[..duration.len() - unit.len_utf8()]
+    .parse()
+    .map_err(|_| invalid_duration())?;
+
+  let unit_secs = match unit {
+    'm' => 60,
+    'h' => 60 * 60,
+    'd' => 24 * 60 * 60,
+    'w' => 7 * 24 * 60 * 60,
+    _ => return Err(invalid_duration()),
+  };
+
+  return amount
+    .checked_mul(unit_secs)
+    .ok_or_else(|| anyhow!("Duration {} is too large", duration));
+}
@@ -0,0 +1,68 @@
+use std::env;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process;
+use std::process::Command;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::anyhow;
+
+use crate::types::ResultAnyError;
+
+/// Open `$VISUAL` or `$EDITOR` (defaults to `vi`) with the given
+/// initial content and return the content once the editor exits.
+pub fn edit(initial_content: &str) -> ResultAnyError<String> {
+  let editor = env::var("VISUAL")
+    .or_else(|_| env::var("EDITOR"))
+    .unwrap_or_else(|_| String::from("vi"));
+
+  let filepath = create_temp_file(initial_content)?;
+
+  // Editor might contain args e.g. `code --wait`
+  let mut editor_parts = editor.split_whitespace();
+  let program = editor_parts.next().unwrap_or("vi");
+
+  let status = Command::new(program)
+    .args(editor_parts)
+    .arg(&filepath)
+    .status();
+
+  let content = status.map_err(anyhow::Error::new).and_then(|status| {
+    if !status.success() {
+      return Err(anyhow!("Editor {} exited with {}", editor, status));
+    }
+
+    return fs::read_to_string(&filepath).map_err(anyhow::Error::new);
+  });
+
+  let _ = fs::remove_file(&filepath);
+
+  return content.map(|content| content.trim_end().to_owned());
+}
+
+/// Create a new file only readable by the current user, an existing file or
+/// symlink at the path is never followed.
+fn create_temp_file(content: &str) -> ResultAnyError<PathBuf> {
+  let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+  let filepath = env::temp_dir().join(format!("phab_edit_{}_{}.md", process::id(), nanos));
+
+  let mut options = OpenOptions::new();
+
+  options.write(true).create_new(true);
+
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    options.mode(0o600);
+  }
+
+  let mut file = options.open(&filepath)?;
+
+  file.write_all(content.as_bytes())?;
+
+  return Ok(filepath);
+}
@@ -0,0 +1,503 @@
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use anyhow::Error;
+use phab_lib::client::phabricator::PhabricatorClient;
+use phab_lib::dto::Task;
+use phab_lib::dto::TaskFamily;
+
+use crate::types::ResultAnyError;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GraphFormat {
+  Dot,
+  Mermaid,
+}
+
+impl GraphFormat {
+  pub const NAMES: [&'static str; 2] = ["dot", "mermaid"];
+}
+
+impl FromStr for GraphFormat {
+  type Err = Error;
+
+  fn from_str(name: &str) -> Result<GraphFormat, Error> {
+    return match name {
+      "dot" => Ok(GraphFormat::Dot),
+      "mermaid" => Ok(GraphFormat::Mermaid),
+      _ => Err(anyhow!(
+        "Invalid graph format {}, valid formats: {}",
+        name,
+        GraphFormat::NAMES.join(", ")
+      )),
+    };
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EdgeKind {
+  /// Parent to subtask, the hierarchy of the task family.
+  Subtask,
+  /// Task to a parent outside of the task family, the task blocks that parent as well.
+  Blocking,
+  /// Task mentions or is mentioned by another task, drawn without direction.
+  Related,
+}
+
+impl EdgeKind {
+  /// Kinds that can be added on top of the task family hierarchy.
+  pub const OPTIONAL_NAMES: [&'static str; 2] = ["blocking", "related"];
+}
+
+impl FromStr for EdgeKind {
+  type Err = Error;
+
+  fn from_str(name: &str) -> Result<EdgeKind, Error> {
+    return match name {
+      "blocking" => Ok(EdgeKind::Blocking),
+      "related" => Ok(EdgeKind::Related),
+      _ => Err(anyhow!(
+        "Invalid edge kind {}, valid kinds: {}",
+        name,
+        EdgeKind::OPTIONAL_NAMES.join(", ")
+      )),
+    };
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphEdge {
+  pub from_phid: String,
+  pub to_phid: String,
+  pub kind: EdgeKind,
+}
+
+/// Tasks and the edges between them, invalid tasks are left out like in `phab task detail`.
+/// ```
+/// # use phab_lib::dto::Task;
+/// # use phab_lib::dto::TaskFamily;
+/// use lib::graph::TaskGraph;
+///
+/// let mut epic = Task::fake(1, "Epic");
+/// let mut login = Task::fake(2, "Login \"v2\"");
+///
+/// epic.point = Some(3.0);
+/// login.point = Some(3.0);
+/// login.status = "resolved".into();
+///
+/// let graph = TaskGraph::from_task_family(&TaskFamily::new(epic, vec![
+///   TaskFamily::new(login, vec![]),
+/// ]));
+///
+/// assert_eq!(graph.to_mermaid(), r##"graph TD
+///   T1["T1 Epic<br/>3pt"]
+///   T2["T2 Login #quot;v2#quot;<br/>3pt"]
+///   T1 --> T2
+///   classDef open fill:#fff2cc,stroke:#bf9000
+///   classDef resolved fill:#d9ead3,stroke:#38761d
+///   class T1 open
+///   class T2 resolved
+/// "##);
+///
+/// assert_eq!(graph.to_dot(), r##"digraph tasks {
+///   node [shape=box, style="rounded,filled"];
+///   "T1" [label="T1 Epic\n3pt", fillcolor="#fff2cc"];
+///   "T2" [label="T2 Login \"v2\"\n3pt", fillcolor="#d9ead3"];
+///   "T1" -> "T2";
+/// }
+/// "##);
+/// ```
+pub struct TaskGraph {
+  pub tasks: Vec<Task>,
+  pub edges: Vec<GraphEdge>,
+}
+
+impl TaskGraph {
+  pub fn from_task_family(task_family: &TaskFamily) -> TaskGraph {
+    let mut graph = TaskGraph {
+      tasks: vec![],
+      edges: vec![],
+    };
+
+    graph.add_task_family(task_family);
+
+    // Shared subtasks and cycles are only referenced by id, they're listed elsewhere in the family
+    let mut referenced_edges = vec![];
+
+    collect_referenced_edges(task_family, &graph.tasks, &mut referenced_edges);
+    graph.add_edges(referenced_edges);
+
+    return graph;
+  }
+
+  fn add_task_family(&mut self, task_family: &TaskFamily) {
+    let task = &task_family.parent_task;
+
+    if task.status == "invalid" || self.has_task(&task.phid) {
+      return;
+    }
+
+    self.tasks.push(task.clone());
+
+    for child in task_family.children.iter() {
+      if child.parent_task.status == "invalid" {
+        continue;
+      }
+
+      self.add_edges(vec![GraphEdge {
+        from_phid: task.phid.clone(),
+        to_phid: child.parent_task.phid.clone(),
+        kind: EdgeKind::Subtask,
+      }]);
+
+      self.add_task_family(child);
+    }
+  }
+
+  pub fn has_task(&self, phid: &str) -> bool {
+    return self.tasks.iter().any(|task| task.phid == phid);
+  }
+
+  /// Edges that are already in the graph, in either direction, are skipped.
+  fn add_edges(&mut self, edges: Vec<GraphEdge>) {
+    for edge in edges {
+      let exists = self.edges.iter().any(|existing| {
+        return (existing.from_phid == edge.from_phid && existing.to_phid == edge.to_phid)
+          || (existing.from_phid == edge.to_phid && existing.to_phid == edge.from_phid);
+      });
+
+      if !exists {
+        self.edges.push(edge);
+      }
+    }
+  }
+
+  /// Fetch edges of the given kind for every task in the graph, tasks outside
+  /// of the graph that they point to are fetched and added as well.
+  pub async fn fetch_edges(
+    &mut self,
+    phabricator: &PhabricatorClient,
+    kind: EdgeKind,
+  ) -> ResultAnyError<()> {
+    let edge_types = match kind {
+      EdgeKind::Subtask => vec!["task.subtask"],
+      EdgeKind::Blocking => vec!["task.parent"],
+      EdgeKind::Related => vec!["mention", "mentioned-in"],
+    };
+
+    let task_phids: Vec<&str> = self.tasks.iter().map(|task| task.phid.as_str()).collect();
+    let edges: Vec<GraphEdge> = phabricator
+      .get_edges(task_phids, edge_types)
+      .await?
+      .into_iter()
+      // Mentions can be any object e.g. revisions
+      .filter(|edge| edge.destination_phid.starts_with("PHID-TASK-"))
+      .map(|edge| GraphEdge {
+        from_phid: edge.source_phid,
+        to_phid: edge.destination_phid,
+        kind,
+      })
+      .collect();
+
+    let mut missing_phids: Vec<&str> = edges
+      .iter()
+      .map(|edge| edge.to_phid.as_str())
+      .filter(|phid| !self.has_task(phid))
+      .collect();
+
+    missing_phids.sort_unstable();
+    missing_phids.dedup();
+
+    if !missing_phids.is_empty() {
+      let mut tasks = phabricator.get_tasks_by_phids(missing_phids).await?;
+
+      phabricator
+        .fetch_tasks_users(tasks.iter_mut().collect())
+        .await?;
+
+      self
+        .tasks
+        .extend(tasks.into_iter().filter(|task| task.status != "invalid"));
+    }
+
+    let edges = edges
+      .into_iter()
+      .filter(|edge| self.has_task(&edge.to_phid))
+      .collect();
+
+    self.add_edges(edges);
+
+    return Ok(());
+  }
+
+  pub fn format(&self, format: GraphFormat) -> String {
+    return match format {
+      GraphFormat::Dot => self.to_dot(),
+      GraphFormat::Mermaid => self.to_mermaid(),
+    };
+  }
+
+  /// Graphviz digraph, render it with e.g. `dot -Tsvg`.
+  pub fn to_dot(&self) -> String {
+    let mut output = String::from("digraph tasks {\n");
+
+    output.push_str("  node [shape=box, style=\"rounded,filled\"];\n");
+
+    for task in self.tasks.iter() {
+      let label = node_label_lines(task)
+        .iter()
+        .map(|line| line.replace('\\', "\\\\").replace('"', "\\\""))
+        .collect::<Vec<String>>()
+        .join("\\n");
+
+      output.push_str(&format!(
+        "  \"T{}\" [label=\"{}\", fillcolor=\"{}\"];\n",
+        task.id,
+        label,
+        status_class(&task.status).fill
+      ));
+    }
+
+    for edge in self.edges.iter() {
+      let attributes = match edge.kind {
+        EdgeKind::Subtask => "",
+        EdgeKind::Blocking => " [style=dashed, label=\"blocks\"]",
+        EdgeKind::Related => " [style=dotted, dir=none]",
+      };
+
+      output.push_str(&format!(
+        "  \"T{}\" -> \"T{}\"{};\n",
+        self.task_id(&edge.from_phid),
+        self.task_id(&edge.to_phid),
+        attributes
+      ));
+    }
+
+    output.push_str("}\n");
+
+    return output;
+  }
+
+  /// Mermaid flowchart, it can be pasted into markdown docs as a `mermaid` code block.
+  pub fn to_mermaid(&self) -> String {
+    let mut output = String::from("graph TD\n");
+
+    for task in self.tasks.iter() {
+      let label = node_label_lines(task)
+        .iter()
+        .map(|line| line.replace('"', "#quot;"))
+        .collect::<Vec<String>>()
+        .join("<br/>");
+
+      output.push_str(&format!("  T{}[\"{}\"]\n", task.id, label));
+    }
+
+    for edge in self.edges.iter() {
+      let arrow = match edge.kind {
+        EdgeKind::Subtask => "-->",
+        EdgeKind::Blocking => "-. blocks .->",
+        EdgeKind::Related => "-.-",
+      };
+
+      output.push_str(&format!(
+        "  T{} {} T{}\n",
+        self.task_id(&edge.from_phid),
+        arrow,
+        self.task_id(&edge.to_phid)
+      ));
+    }
+
+    let mut classes: Vec<&StatusClass> = vec![];
+
+    for task in self.tasks.iter() {
+      let class = status_class(&task.status);
+
+      if !classes.iter().any(|existing| existing.name == class.name) {
+        classes.push(class);
+      }
+    }
+
+    for class in classes.iter() {
+      output.push_str(&format!(
+        "  classDef {} fill:{},stroke:{}\n",
+        class.name, class.fill, class.stroke
+      ));
+    }
+
+    for task in self.tasks.iter() {
+      output.push_str(&format!(
+        "  class T{} {}\n",
+        task.id,
+        status_class(&task.status).name
+      ));
+    }
+
+    return output;
+  }
+
+  fn task_id<'a>(&'a self, phid: &'a str) -> &'a str {
+    return self
+      .tasks
+      .iter()
+      .find(|task| task.phid == phid)
+      .map(|task| task.id.as_str())
+      .unwrap_or(phid);
+  }
+}
+
+fn collect_referenced_edges(task_family: &TaskFamily, tasks: &[Task], edges: &mut Vec<GraphEdge>) {
+  for task_id in task_family.referenced_children.iter() {
+    if let Some(task) = tasks.iter().find(|task| &task.id == task_id) {
+      edges.push(GraphEdge {
+        from_phid: task_family.parent_task.phid.clone(),
+        to_phid: task.phid.clone(),
+        kind: EdgeKind::Subtask,
+      });
+    }
+  }
+
+  for child in task_family.children.iter() {
+    collect_referenced_edges(child, tasks, edges);
+  }
+}
+
+/// Title on the first line, points and owner on the second one if any.
+fn node_label_lines(task: &Task) -> Vec<String> {
+  let mut details = vec![];
+
+  if let Some(point) = task.point {
+    details.push(format!("{}pt", point));
+  }
+
+  if let Some(user) = &task.assigned {
+    details.push(user.mention());
+  }
+
+  let mut lines = vec![format!("T{} {}", task.id, task.name)];
+
+  if !details.is_empty() {
+    lines.push(details.join(" "));
+  }
+
+  return lines;
+}
+
+struct StatusClass {
+  name: &'static str,
+  fill: &'static str,
+  stroke: &'static str,
+}
+
+static STATUS_CLASSES: [StatusClass; 4] = [
+  StatusClass {
+    name: "open",
+    fill: "#fff2cc",
+    stroke: "#bf9000",
+  },
+  StatusClass {
+    name: "resolved",
+    fill: "#d9ead3",
+    stroke: "#38761d",
+  },
+  StatusClass {
+    name: "closed",
+    fill: "#eeeeee",
+    stroke: "#999999",
+  },
+  // Custom statuses, e.g. `progress` or `stalled`
+  StatusClass {
+    name: "other",
+    fill: "#cfe2f3",
+    stroke: "#3d85c6",
+  },
+];
+
+fn status_class(status: &str) -> &'static StatusClass {
+  return match status {
+    "open" => &STATUS_CLASSES[0],
+    "resolved" => &STATUS_CLASSES[1],
+    "wontfix" | "invalid" | "duplicate" | "spite" => &STATUS_CLASSES[2],
+    _ => &STATUS_CLASSES[3],
+  };
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::Arc;
+
+  use phab_lib::client::transport::FakeTransport;
+  use serde_json::json;
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_fetch_related_edges_from_mentions() {
+    let mut graph = TaskGraph::from_task_family(&TaskFamily::new(
+      Task::fake(1, "Epic"),
+      vec![TaskFamily::new(Task::fake(2, "Login"), vec![])],
+    ));
+
+    let transport = Arc::new(
+      FakeTransport::new()
+        .with_result(
+          "edge.search",
+          json!({
+            "data": [
+              { "sourcePHID": "PHID-TASK-1", "edgeType": "mention", "destinationPHID": "PHID-DREV-1" },
+              { "sourcePHID": "PHID-TASK-1", "edgeType": "mention", "destinationPHID": "PHID-TASK-2" },
+              { "sourcePHID": "PHID-TASK-2", "edgeType": "mentioned-in", "destinationPHID": "PHID-TASK-5" },
+            ],
+            "cursor": { "after": null },
+          }),
+        )
+        .with_result(
+          "maniphest.search",
+          json!({ "data": [Task::fake_json(5, "Logout")], "cursor": { "after": null } }),
+        )
+        .with_result(
+          "user.search",
+          json!({
+            "data": [{
+              "id": 1,
+              "phid": "PHID-USER-1",
+              "fields": { "username": "alice", "realName": null, "dateCreated": 0, "dateModified": 0 },
+            }],
+            "cursor": { "after": null },
+          }),
+        ),
+    );
+
+    let phabricator = PhabricatorClient::with_transport(transport.clone());
+
+    graph
+      .fetch_edges(&phabricator, EdgeKind::Related)
+      .await
+      .unwrap();
+
+    let calls = transport.calls();
+    let edge_search = calls
+      .iter()
+      .find(|(method, _)| method == "edge.search")
+      .unwrap();
+
+    assert_eq!(edge_search.1["types"], json!(["mention", "mentioned-in"]));
+    assert!(graph.has_task("PHID-TASK-5"));
+    // The mention between T1 and its subtask is already drawn as a subtask edge
+    assert_eq!(
+      graph.edges,
+      vec![
+        GraphEdge {
+          from_phid: "PHID-TASK-1".into(),
+          to_phid: "PHID-TASK-2".into(),
+          kind: EdgeKind::Subtask,
+        },
+        GraphEdge {
+          from_phid: "PHID-TASK-2".into(),
+          to_phid: "PHID-TASK-5".into(),
+          kind: EdgeKind::Related,
+        },
+      ]
+    );
+    assert!(graph.to_mermaid().contains("  T2 -.- T5\n"));
+  }
+}
@@ -0,0 +1,201 @@
+use phab_lib::dto::Task;
+use phab_lib::dto::Workboard;
+use unicode_width::UnicodeWidthChar;
+use unicode_width::UnicodeWidthStr;
+
+/// Lanes narrower than this are moved to the next row instead.
+const MIN_LANE_WIDTH: usize = 20;
+const LANE_SEPARATOR: &str = " │ ";
+/// Longer task titles are truncated.
+const MAX_TITLE_LINES: usize = 2;
+
+pub struct Lane {
+  pub name: String,
+  pub cards: Vec<Card>,
+}
+
+pub struct Card {
+  /// Short task summary, e.g. `T123 3pt @alice`.
+  pub header: String,
+  pub title: String,
+}
+
+impl Card {
+  pub fn from_task(task: &Task) -> Card {
+    let mut header = format!("T{}", task.id);
+
+    if let Some(point) = task.point {
+      header.push_str(&format!(" {}pt", point));
+    }
+
+    if let Some(assigned) = &task.assigned {
+      header.push_str(&format!(" {}", assigned.mention()));
+    }
+
+    return Card {
+      header,
+      title: task.name.clone(),
+    };
+  }
+}
+
+pub fn lanes_of(workboard: &Workboard) -> Vec<Lane> {
+  return workboard
+    .lanes
+    .iter()
+    .map(|lane| Lane {
+      name: format!("{} ({})", lane.column.name, lane.tasks.len()),
+      cards: lane.tasks.iter().map(Card::from_task).collect(),
+    })
+    .collect();
+}
+
+/// Render lanes side by side within `width` columns, lanes that don't fit
+/// are rendered in the next rows.
+/// ```
+/// use lib::kanban::render_lanes;
+/// use lib::kanban::Card;
+/// use lib::kanban::Lane;
+///
+/// let lanes = vec![
+///   Lane { name: "Todo".into(), cards: vec![Card { header: "T1 @bob".into(), title: "Fix login".into() }] },
+///   Lane { name: "Done".into(), cards: vec![] },
+/// ];
+///
+/// assert_eq!(render_lanes(&lanes, 43), vec![
+///   "Todo                 │ Done                ",
+///   "──────────────────── │ ────────────────────",
+///   "T1 @bob              │                     ",
+///   "Fix login            │                     ",
+/// ]);
+/// ```
+pub fn render_lanes(lanes: &[Lane], width: usize) -> Vec<String> {
+  let separator_width = LANE_SEPARATOR.width();
+  let lanes_per_row = ((width + separator_width) / (MIN_LANE_WIDTH + separator_width))
+    .max(1)
+    .min(lanes.len().max(1));
+
+  let lane_width = (width.saturating_sub((lanes_per_row - 1) * separator_width) / lanes_per_row)
+    .max(MIN_LANE_WIDTH.min(width));
+
+  let mut lines = vec![];
+
+  for (row, row_lanes) in lanes.chunks(lanes_per_row).enumerate() {
+    if row > 0 {
+      lines.push(String::new());
+    }
+
+    let lane_lines: Vec<Vec<String>> = row_lanes
+      .iter()
+      .map(|lane| render_lane(lane, lane_width))
+      .collect();
+
+    let height = lane_lines.iter().map(Vec::len).max().unwrap_or_default();
+
+    for i in 0..height {
+      let line: Vec<String> = lane_lines
+        .iter()
+        .map(|lane_lines| {
+          return lane_lines
+            .get(i)
+            .cloned()
+            .unwrap_or_else(|| " ".repeat(lane_width));
+        })
+        .collect();
+
+      lines.push(line.join(LANE_SEPARATOR));
+    }
+  }
+
+  return lines;
+}
+
+fn render_lane(lane: &Lane, width: usize) -> Vec<String> {
+  let mut lines = vec![fit(&lane.name, width), "─".repeat(width)];
+
+  for (i, card) in lane.cards.iter().enumerate() {
+    if i > 0 {
+      lines.push(" ".repeat(width));
+    }
+
+    lines.push(fit(&card.header, width));
+    lines.extend(wrap(&card.title, width, MAX_TITLE_LINES));
+  }
+
+  return lines;
+}
+
+/// Truncate or pad the text to exactly `width` columns.
+/// ```
+/// use lib::kanban::fit;
+///
+/// assert_eq!(fit("Fix login", 12), "Fix login   ");
+/// assert_eq!(fit("Fix login page", 8), "Fix log…");
+/// ```
+pub fn fit(text: &str, width: usize) -> String {
+  let mut fitted = String::new();
+  let mut fitted_width = 0;
+
+  if text.width() > width {
+    for c in text.chars() {
+      let char_width = c.width().unwrap_or(0);
+
+      // Leave room for the ellipsis
+      if fitted_width + char_width + 1 > width {
+        break;
+      }
+
+      fitted.push(c);
+      fitted_width += char_width;
+    }
+
+    if width > 0 {
+      fitted.push('…');
+      fitted_width += 1;
+    }
+  } else {
+    fitted.push_str(text);
+    fitted_width = text.width();
+  }
+
+  fitted.push_str(&" ".repeat(width.saturating_sub(fitted_width)));
+
+  return fitted;
+}
+
+/// Word wrap the text into at most `max_lines` lines of `width` columns.
+/// ```
+/// use lib::kanban::wrap;
+///
+/// assert_eq!(wrap("Fix the login page", 10, 2), vec!["Fix the   ", "login page"]);
+/// assert_eq!(wrap("Fix the login page again", 10, 2), vec!["Fix the   ", "login pag…"]);
+/// ```
+pub fn wrap(text: &str, width: usize, max_lines: usize) -> Vec<String> {
+  let mut lines: Vec<String> = vec![];
+  let mut line = String::new();
+
+  for word in text.split_whitespace() {
+    if !line.is_empty() && line.width() + 1 + word.width() > width {
+      lines.push(line);
+      line = String::new();
+    }
+
+    if !line.is_empty() {
+      line.push(' ');
+    }
+
+    line.push_str(word);
+  }
+
+  if !line.is_empty() || lines.is_empty() {
+    lines.push(line);
+  }
+
+  if lines.len() > max_lines {
+    let rest = lines.split_off(max_lines - 1).join(" ");
+    // Force the ellipsis, the remaining text never fits the last line
+    lines.push(format!("{}{}", rest, " ".repeat(width)));
+  }
+
+  return lines.iter().map(|line| fit(line, width)).collect();
+}
@@ -1,2 +1,12 @@
+#![allow(clippy::needless_return)]
+
+pub mod comments;
 pub mod config;
+pub mod duration;
+pub mod editor;
+pub mod graph;
+pub mod kanban;
+pub mod output;
+pub mod tui;
 pub mod types;
+pub mod users;
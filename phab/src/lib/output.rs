@@ -0,0 +1,453 @@
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use anyhow::Error;
+use phab_lib::dto::Task;
+use phab_lib::dto::TaskFamily;
+use phab_lib::dto::User;
+use unicode_width::UnicodeWidthStr;
+
+use crate::types::ResultAnyError;
+
+/// Statuses that are checked in markdown checklists.
+const CLOSED_STATUSES: [&str; 5] = ["resolved", "wontfix", "invalid", "duplicate", "spite"];
+
+/// Render task families into a printable string, e.g. the tree of `phab task detail`.
+/// A flat list of tasks is rendered as families without children.
+pub trait TaskFormatter {
+  fn format(&self, task_families: &[TaskFamily]) -> ResultAnyError<String>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+  Tree,
+  Table,
+  Markdown,
+  Csv,
+  Json,
+  Yaml,
+}
+
+impl OutputFormat {
+  pub const NAMES: [&'static str; 6] = ["tree", "table", "markdown", "csv", "json", "yaml"];
+
+  /// `host` is used to link tasks, e.g. in markdown.
+  pub fn formatter(&self, host: &str) -> Box<dyn TaskFormatter> {
+    return match self {
+      OutputFormat::Tree => Box::new(TreeFormatter),
+      OutputFormat::Table => Box::new(TableFormatter),
+      OutputFormat::Markdown => Box::new(MarkdownFormatter {
+        host: host.trim_end_matches('/').to_owned(),
+      }),
+      OutputFormat::Csv => Box::new(CsvFormatter),
+      OutputFormat::Json => Box::new(JsonFormatter),
+      OutputFormat::Yaml => Box::new(YamlFormatter),
+    };
+  }
+}
+
+impl FromStr for OutputFormat {
+  type Err = Error;
+
+  fn from_str(name: &str) -> Result<OutputFormat, Error> {
+    return match name {
+      "tree" => Ok(OutputFormat::Tree),
+      "table" => Ok(OutputFormat::Table),
+      "markdown" => Ok(OutputFormat::Markdown),
+      "csv" => Ok(OutputFormat::Csv),
+      "json" => Ok(OutputFormat::Json),
+      "yaml" => Ok(OutputFormat::Yaml),
+      _ => Err(anyhow!(
+        "Invalid output format {}, valid formats: {}",
+        name,
+        OutputFormat::NAMES.join(", ")
+      )),
+    };
+  }
+}
+
+/// A visible task of the tree, invalid tasks and their subtasks are hidden
+/// like in the default output.
+struct TaskRow<'a> {
+  depth: usize,
+  parent: Option<&'a Task>,
+  task_family: &'a TaskFamily,
+}
+
+fn task_rows(task_families: &[TaskFamily]) -> Vec<TaskRow<'_>> {
+  fn collect<'a>(
+    task_families: &'a [TaskFamily],
+    depth: usize,
+    parent: Option<&'a Task>,
+    rows: &mut Vec<TaskRow<'a>>,
+  ) {
+    for task_family in visible(task_families) {
+      rows.push(TaskRow {
+        depth,
+        parent,
+        task_family,
+      });
+
+      collect(
+        &task_family.children,
+        depth + 1,
+        Some(&task_family.parent_task),
+        rows,
+      );
+    }
+  }
+
+  let mut rows = vec![];
+
+  collect(task_families, 0, None, &mut rows);
+
+  return rows;
+}
+
+fn visible(task_families: &[TaskFamily]) -> Vec<&TaskFamily> {
+  return task_families
+    .iter()
+    .filter(|task_family| task_family.parent_task.status != "invalid")
+    .collect();
+}
+
+fn owner_of(task: &Task) -> String {
+  return task
+    .assigned
+    .as_ref()
+    .map(User::mention)
+    .unwrap_or_default();
+}
+
+/// One line summary, e.g. `T12 [open] Login page 3pt @alice`.
+fn task_summary(task: &Task) -> String {
+  let mut summary = format!("T{} [{}] {}", task.id, task.status, task.name);
+
+  if let Some(point) = task.point {
+    summary.push_str(&format!(" {}pt", point));
+  }
+
+  if let Some(user) = &task.assigned {
+    summary.push_str(&format!(" {}", user.mention()));
+  }
+
+  return summary;
+}
+
+/// Tree drawn with box-drawing characters, subtasks that are listed elsewhere
+/// in the tree are only referenced.
+/// ```
+/// # use phab_lib::dto::Task;
+/// # use phab_lib::dto::TaskFamily;
+/// use lib::output::OutputFormat;
+///
+/// let task_families = vec![TaskFamily::new(Task::fake(1, "Epic"), vec![
+///   TaskFamily::new(Task::fake(2, "Login"), vec![
+///     TaskFamily::new(Task::fake(3, "Login api"), vec![]),
+///   ]),
+///   TaskFamily::new(Task::fake(4, "Logout"), vec![]),
+/// ])];
+///
+/// let tree = OutputFormat::Tree.formatter("").format(&task_families).unwrap();
+///
+/// assert_eq!(tree, "\
+/// T1 [open] Epic
+/// ├── T2 [open] Login
+/// │   └── T3 [open] Login api
+/// └── T4 [open] Logout
+/// ");
+/// ```
+pub struct TreeFormatter;
+
+impl TreeFormatter {
+  fn format_children(task_family: &TaskFamily, prefix: &str, output: &mut String) {
+    let children = visible(&task_family.children);
+    let referenced_count = task_family.referenced_children.len();
+    let line_count = children.len() + referenced_count;
+
+    for (i, child) in children.iter().enumerate() {
+      let is_last = i + 1 == line_count;
+      let (branch, indentation) = if is_last {
+        ("└── ", "    ")
+      } else {
+        ("├── ", "│   ")
+      };
+
+      output.push_str(&format!(
+        "{}{}{}\n",
+        prefix,
+        branch,
+        task_summary(&child.parent_task)
+      ));
+
+      TreeFormatter::format_children(child, &format!("{}{}", prefix, indentation), output);
+    }
+
+    for (i, task_id) in task_family.referenced_children.iter().enumerate() {
+      let branch = if i + 1 == referenced_count {
+        "└── "
+      } else {
+        "├── "
+      };
+
+      output.push_str(&format!(
+        "{}{}T{} (listed above)\n",
+        prefix, branch, task_id
+      ));
+    }
+  }
+}
+
+impl TaskFormatter for TreeFormatter {
+  fn format(&self, task_families: &[TaskFamily]) -> ResultAnyError<String> {
+    let mut output = String::new();
+
+    for task_family in visible(task_families) {
+      output.push_str(&format!("{}\n", task_summary(&task_family.parent_task)));
+      TreeFormatter::format_children(task_family, "", &mut output);
+    }
+
+    return Ok(output);
+  }
+}
+
+/// Aligned columns, titles are indented by their depth in the tree.
+pub struct TableFormatter;
+
+impl TaskFormatter for TableFormatter {
+  fn format(&self, task_families: &[TaskFamily]) -> ResultAnyError<String> {
+    let header = ["ID", "STATUS", "POINTS", "OWNER", "BOARDS", "TITLE"];
+    let mut rows: Vec<Vec<String>> = vec![header.iter().map(|name| name.to_string()).collect()];
+
+    for row in task_rows(task_families) {
+      let task = &row.task_family.parent_task;
+
+      rows.push(vec![
+        format!("T{}", task.id),
+        task.status.clone(),
+        task
+          .point
+          .map(|point| point.to_string())
+          .unwrap_or_default(),
+        owner_of(task),
+        task.board_labels().join(", "),
+        format!("{}{}", "  ".repeat(row.depth), task.name),
+      ]);
+    }
+
+    let widths: Vec<usize> = (0..header.len())
+      .map(|column| {
+        rows
+          .iter()
+          .map(|row| row[column].width())
+          .max()
+          .unwrap_or(0)
+      })
+      .collect();
+
+    let mut output = String::new();
+
+    for row in rows {
+      let line: Vec<String> = row
+        .iter()
+        .zip(widths.iter())
+        .map(|(cell, width)| format!("{}{}", cell, " ".repeat(width - cell.width())))
+        .collect();
+
+      output.push_str(line.join("  ").trim_end());
+      output.push('\n');
+    }
+
+    return Ok(output);
+  }
+}
+
+/// Nested checklist, closed tasks are checked and every task links to phabricator.
+pub struct MarkdownFormatter {
+  host: String,
+}
+
+impl TaskFormatter for MarkdownFormatter {
+  fn format(&self, task_families: &[TaskFamily]) -> ResultAnyError<String> {
+    let mut output = String::new();
+
+    for row in task_rows(task_families) {
+      let task = &row.task_family.parent_task;
+      let checkbox = if CLOSED_STATUSES.contains(&task.status.as_str()) {
+        "[x]"
+      } else {
+        "[ ]"
+      };
+
+      let owner = owner_of(task);
+      let owner = if owner.is_empty() {
+        owner
+      } else {
+        format!(" {}", owner)
+      };
+
+      output.push_str(&format!(
+        "{}- {} [T{}]({}/T{}) {}{}\n",
+        "  ".repeat(row.depth),
+        checkbox,
+        task.id,
+        self.host,
+        task.id,
+        task.name,
+        owner
+      ));
+    }
+
+    return Ok(output);
+  }
+}
+
+/// One row per task, the tree is kept with depth and parent id columns.
+pub struct CsvFormatter;
+
+impl TaskFormatter for CsvFormatter {
+  fn format(&self, task_families: &[TaskFamily]) -> ResultAnyError<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+
+    writer.write_record([
+      "id",
+      "parent_id",
+      "depth",
+      "status",
+      "priority",
+      "points",
+      "owner",
+      "boards",
+      "title",
+    ])?;
+
+    for row in task_rows(task_families) {
+      let task = &row.task_family.parent_task;
+
+      writer.write_record([
+        task.id.clone(),
+        row
+          .parent
+          .map(|parent| parent.id.clone())
+          .unwrap_or_default(),
+        row.depth.to_string(),
+        task.status.clone(),
+        task.priority.clone(),
+        task
+          .point
+          .map(|point| point.to_string())
+          .unwrap_or_default(),
+        owner_of(task),
+        task.board_labels().join(", "),
+        task.name.clone(),
+      ])?;
+    }
+
+    return Ok(String::from_utf8(writer.into_inner()?)?);
+  }
+}
+
+/// Same json as `--print-json`.
+pub struct JsonFormatter;
+
+impl TaskFormatter for JsonFormatter {
+  fn format(&self, task_families: &[TaskFamily]) -> ResultAnyError<String> {
+    return TaskFamily::json_string(task_families).map(|json| format!("{}\n", json));
+  }
+}
+
+/// Same fields as the json output.
+pub struct YamlFormatter;
+
+impl TaskFormatter for YamlFormatter {
+  fn format(&self, task_families: &[TaskFamily]) -> ResultAnyError<String> {
+    return serde_yaml::to_string(task_families).map_err(Error::new);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use phab_lib::dto::Board;
+
+  use super::*;
+
+  /// T1 Epic with a resolved T2 Login subtask that has points, an owner and a board.
+  fn task_families() -> Vec<TaskFamily> {
+    let mut login = Task::fake(2, "Login");
+
+    login.status = "resolved".into();
+    login.point = Some(2.5);
+    login.assigned = Some(User {
+      id: "1".into(),
+      phid: "PHID-USER-1".into(),
+      username: "alice".into(),
+      name: "Alice".into(),
+      created_at: 0,
+      updated_at: 0,
+    });
+    login.boards = vec![Board {
+      id: 1,
+      phid: "PHID-PCOL-1".into(),
+      name: "Backlog".into(),
+      project_phid: "PHID-PROJ-1".into(),
+    }];
+
+    return vec![TaskFamily::new(
+      Task::fake(1, "Epic"),
+      vec![TaskFamily::new(login, vec![])],
+    )];
+  }
+
+  fn format(output_format: OutputFormat, host: &str) -> String {
+    return output_format
+      .formatter(host)
+      .format(&task_families())
+      .unwrap();
+  }
+
+  #[test]
+  fn test_table_indents_titles_by_depth() {
+    assert_eq!(
+      format(OutputFormat::Table, ""),
+      "\
+ID  STATUS    POINTS  OWNER   BOARDS   TITLE
+T1  open                               Epic
+T2  resolved  2.5     @alice  Backlog    Login
+"
+    );
+  }
+
+  #[test]
+  fn test_markdown_checks_closed_tasks() {
+    assert_eq!(
+      format(OutputFormat::Markdown, "https://phab.example.com/"),
+      "\
+- [ ] [T1](https://phab.example.com/T1) Epic
+  - [x] [T2](https://phab.example.com/T2) Login @alice
+"
+    );
+  }
+
+  #[test]
+  fn test_csv_keeps_the_tree_in_parent_and_depth_columns() {
+    assert_eq!(
+      format(OutputFormat::Csv, ""),
+      "\
+id,parent_id,depth,status,priority,points,owner,boards,title
+1,,0,open,Needs Triage,,,,Epic
+2,1,1,resolved,Needs Triage,2.5,@alice,Backlog,Login
+"
+    );
+  }
+
+  #[test]
+  fn test_yaml_roundtrips_task_families() {
+    let parsed: Vec<TaskFamily> = serde_yaml::from_str(&format(OutputFormat::Yaml, "")).unwrap();
+    let login = &parsed[0].children[0].parent_task;
+
+    assert_eq!(parsed[0].parent_task.name, "Epic");
+    assert_eq!(login.point, Some(2.5));
+    assert_eq!(login.assigned.as_ref().unwrap().username, "alice");
+    assert_eq!(login.boards[0].name, "Backlog");
+  }
+}
@@ -0,0 +1,459 @@
+pub mod tree;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use phab_lib::client::phabricator::PhabricatorClient;
+use phab_lib::client::task_edit::TaskTransaction;
+use phab_lib::dto::Comment;
+use phab_lib::dto::TaskFamily;
+use phab_lib::dto::User;
+use ratatui::crossterm::event;
+use ratatui::crossterm::event::Event;
+use ratatui::crossterm::event::KeyCode;
+use ratatui::crossterm::event::KeyEvent;
+use ratatui::crossterm::event::KeyEventKind;
+use ratatui::crossterm::event::KeyModifiers;
+use ratatui::layout::Constraint;
+use ratatui::layout::Layout;
+use ratatui::layout::Rect;
+use ratatui::style::Color;
+use ratatui::style::Modifier;
+use ratatui::style::Style;
+use ratatui::text::Line;
+use ratatui::text::Span;
+use ratatui::widgets::Block;
+use ratatui::widgets::List;
+use ratatui::widgets::ListItem;
+use ratatui::widgets::ListState;
+use ratatui::widgets::Paragraph;
+use ratatui::widgets::Wrap;
+use ratatui::DefaultTerminal;
+use ratatui::Frame;
+
+use crate::comments::comment_header;
+use crate::tui::tree::TaskTreeView;
+use crate::types::ResultAnyError;
+use crate::users::resolve_user_phid;
+
+/// Comments of the selected task are fetched once it stays selected this long,
+/// moving through the tree doesn't wait for the comments of every task on the way.
+const COMMENTS_DELAY: Duration = Duration::from_millis(300);
+const DETAIL_SCROLL_LINES: u16 = 10;
+const HELP: &str =
+  "j/k move  h/l collapse/expand  / filter  s status  a assign  PgUp/PgDn scroll  q quit";
+
+#[derive(Clone, Copy)]
+enum Mode {
+  Browse,
+  Filter,
+  Prompt(Prompt),
+}
+
+#[derive(Clone, Copy)]
+enum Prompt {
+  Status,
+  Assign,
+}
+
+impl Prompt {
+  fn label(&self) -> &'static str {
+    return match self {
+      Prompt::Status => "Status (open, resolved, wontfix, invalid): ",
+      Prompt::Assign => "Assign to (username, me, empty to unassign): ",
+    };
+  }
+}
+
+/// Browse the task family in a full screen terminal ui until the user quits.
+pub async fn run(phabricator: &PhabricatorClient, task_family: TaskFamily) -> ResultAnyError<()> {
+  let mut terminal = ratatui::try_init()?;
+  let mut app = App::new(phabricator, task_family);
+  let result = app.run(&mut terminal).await;
+
+  ratatui::restore();
+
+  return result;
+}
+
+struct App<'a> {
+  phabricator: &'a PhabricatorClient,
+  tree: TaskTreeView,
+  mode: Mode,
+  /// Prompt input, the filter input is kept by the tree.
+  input: String,
+  /// Comments by task id, failures are kept as well so they're not refetched on every tick.
+  comments: HashMap<String, Result<Vec<Comment>, String>>,
+  /// Result of the last edit, shown until the next key press.
+  message: String,
+  detail_scroll: u16,
+  should_quit: bool,
+}
+
+impl<'a> App<'a> {
+  fn new(phabricator: &'a PhabricatorClient, task_family: TaskFamily) -> App<'a> {
+    return App {
+      phabricator,
+      tree: TaskTreeView::new(task_family),
+      mode: Mode::Browse,
+      input: String::new(),
+      comments: HashMap::new(),
+      message: String::new(),
+      detail_scroll: 0,
+      should_quit: false,
+    };
+  }
+
+  async fn run(&mut self, terminal: &mut DefaultTerminal) -> ResultAnyError<()> {
+    while !self.should_quit {
+      terminal.draw(|frame| self.draw(frame))?;
+
+      if !tokio::task::block_in_place(|| event::poll(COMMENTS_DELAY))? {
+        self.fetch_selected_comments().await;
+
+        continue;
+      }
+
+      if let Event::Key(key) = tokio::task::block_in_place(event::read)? {
+        if key.kind == KeyEventKind::Press {
+          self.handle_key(key).await;
+        }
+      }
+    }
+
+    return Ok(());
+  }
+
+  async fn fetch_selected_comments(&mut self) {
+    let task_id = match self.tree.selected_task() {
+      Some(task) if !self.comments.contains_key(&task.id) => task.id.clone(),
+      _ => return,
+    };
+
+    let comments = self
+      .phabricator
+      .get_task_comments(&task_id)
+      .await
+      .map_err(|err| err.to_string());
+
+    self.comments.insert(task_id, comments);
+  }
+
+  async fn handle_key(&mut self, key: KeyEvent) {
+    if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+      self.should_quit = true;
+
+      return;
+    }
+
+    match self.mode {
+      Mode::Browse => self.handle_browse_key(key),
+      Mode::Filter => self.handle_filter_key(key),
+      Mode::Prompt(prompt) => self.handle_prompt_key(prompt, key).await,
+    }
+  }
+
+  fn handle_browse_key(&mut self, key: KeyEvent) {
+    let selected = self.tree.selected();
+
+    self.message.clear();
+
+    match key.code {
+      KeyCode::Char('q') => self.should_quit = true,
+      KeyCode::Esc if !self.tree.filter().is_empty() => self.tree.set_filter(""),
+      KeyCode::Esc => self.should_quit = true,
+      KeyCode::Down | KeyCode::Char('j') => self.tree.select_next(),
+      KeyCode::Up | KeyCode::Char('k') => self.tree.select_previous(),
+      KeyCode::Right | KeyCode::Char('l') => self.tree.expand(),
+      KeyCode::Left | KeyCode::Char('h') => self.tree.collapse(),
+      KeyCode::Enter | KeyCode::Char(' ') => self.tree.toggle(),
+      KeyCode::PageDown => {
+        self.detail_scroll = self.detail_scroll.saturating_add(DETAIL_SCROLL_LINES);
+      }
+      KeyCode::PageUp => {
+        self.detail_scroll = self.detail_scroll.saturating_sub(DETAIL_SCROLL_LINES);
+      }
+      KeyCode::Char('/') => self.mode = Mode::Filter,
+      KeyCode::Char('s') => self.start_prompt(Prompt::Status),
+      KeyCode::Char('a') => self.start_prompt(Prompt::Assign),
+      _ => {}
+    }
+
+    if self.tree.selected() != selected {
+      self.detail_scroll = 0;
+    }
+  }
+
+  /// The tree is filtered on every key press.
+  fn handle_filter_key(&mut self, key: KeyEvent) {
+    let mut filter = self.tree.filter().to_owned();
+
+    match key.code {
+      KeyCode::Enter => {
+        self.mode = Mode::Browse;
+
+        return;
+      }
+      KeyCode::Esc => {
+        filter.clear();
+        self.mode = Mode::Browse;
+      }
+      KeyCode::Backspace => {
+        filter.pop();
+      }
+      KeyCode::Char(c) => filter.push(c),
+      _ => return,
+    }
+
+    self.tree.set_filter(&filter);
+    self.detail_scroll = 0;
+  }
+
+  fn start_prompt(&mut self, prompt: Prompt) {
+    if self.tree.selected_task().is_some() {
+      self.input.clear();
+      self.mode = Mode::Prompt(prompt);
+    }
+  }
+
+  async fn handle_prompt_key(&mut self, prompt: Prompt, key: KeyEvent) {
+    match key.code {
+      KeyCode::Esc => self.mode = Mode::Browse,
+      KeyCode::Backspace => {
+        self.input.pop();
+      }
+      KeyCode::Char(c) => self.input.push(c),
+      KeyCode::Enter => {
+        let input = std::mem::take(&mut self.input);
+
+        self.mode = Mode::Browse;
+        self.message = match self.edit_selected_task(prompt, input.trim()).await {
+          Ok(message) => message,
+          Err(err) => format!("Error: {}", err),
+        };
+      }
+      _ => {}
+    }
+  }
+
+  /// Returns the message to show once the task is edited.
+  async fn edit_selected_task(&mut self, prompt: Prompt, input: &str) -> ResultAnyError<String> {
+    let (path, task_id) = match (self.tree.selected_path(), self.tree.selected_task()) {
+      (Some(path), Some(task)) => (path.clone(), task.id.clone()),
+      _ => return Ok(String::new()),
+    };
+
+    let (transaction, message) = match prompt {
+      Prompt::Status if input.is_empty() => return Ok(String::from("Status is not changed")),
+      Prompt::Status => (
+        TaskTransaction::Status(input.to_owned()),
+        format!("T{} status changed to {}", task_id, input),
+      ),
+      Prompt::Assign if input.is_empty() => (
+        TaskTransaction::Owner(None),
+        format!("T{} is unassigned", task_id),
+      ),
+      Prompt::Assign => (
+        TaskTransaction::Owner(Some(resolve_user_phid(self.phabricator, input).await?)),
+        format!("T{} assigned to {}", task_id, input),
+      ),
+    };
+
+    let mut task = self
+      .phabricator
+      .edit_task(&task_id, vec![transaction])
+      .await?;
+
+    self.phabricator.fetch_tasks_users(vec![&mut task]).await?;
+    self
+      .phabricator
+      .fetch_tasks_projects(vec![&mut task])
+      .await?;
+    self.tree.replace_task(&path, task);
+
+    return Ok(message);
+  }
+
+  fn draw(&self, frame: &mut Frame) {
+    let [main_area, status_area] =
+      Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+    let [tree_area, detail_area] =
+      Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(main_area);
+
+    self.draw_tree(frame, tree_area);
+    self.draw_detail(frame, detail_area);
+
+    frame.render_widget(Paragraph::new(self.status_line()), status_area);
+  }
+
+  fn draw_tree(&self, frame: &mut Frame, area: Rect) {
+    let items: Vec<ListItem> = self
+      .tree
+      .rows()
+      .iter()
+      .map(|row| {
+        let task = &self.tree.task_family(&row.path).parent_task;
+        let marker = match (row.has_children, row.is_expanded) {
+          (false, _) => "  ",
+          (true, true) => "▾ ",
+          (true, false) => "▸ ",
+        };
+
+        return ListItem::new(Line::from(vec![
+          Span::raw(format!("{}{}", "  ".repeat(row.depth()), marker)),
+          Span::styled(
+            format!("T{} ", task.id),
+            Style::new().add_modifier(Modifier::BOLD),
+          ),
+          Span::styled(format!("[{}] ", task.status), status_style(&task.status)),
+          Span::raw(task.name.as_str()),
+          Span::styled(
+            task
+              .assigned
+              .as_ref()
+              .map(|user| format!(" {}", user.mention()))
+              .unwrap_or_default(),
+            Style::new().fg(Color::Cyan),
+          ),
+        ]));
+      })
+      .collect();
+
+    let title = if self.tree.filter().is_empty() {
+      String::from(" Tasks ")
+    } else {
+      format!(" Tasks matching \"{}\" ", self.tree.filter())
+    };
+
+    let list = List::new(items)
+      .block(Block::bordered().title(title))
+      .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+
+    let mut state = ListState::default();
+
+    if !self.tree.rows().is_empty() {
+      state.select(Some(self.tree.selected()));
+    }
+
+    frame.render_stateful_widget(list, area, &mut state);
+  }
+
+  fn draw_detail(&self, frame: &mut Frame, area: Rect) {
+    let lines = match self.tree.selected_task_family() {
+      Some(task_family) => self.detail_lines(task_family),
+      None => vec![Line::raw("No matching tasks")],
+    };
+
+    let detail = Paragraph::new(lines)
+      .block(Block::bordered().title(" Detail "))
+      .wrap(Wrap { trim: false })
+      .scroll((self.detail_scroll, 0));
+
+    frame.render_widget(detail, area);
+  }
+
+  fn detail_lines<'t>(&'t self, task_family: &'t TaskFamily) -> Vec<Line<'t>> {
+    let task = &task_family.parent_task;
+    let bold = Style::new().add_modifier(Modifier::BOLD);
+
+    let tags: Vec<String> = task
+      .projects
+      .iter()
+      .map(|project| format!("#{}", project.slug.as_ref().unwrap_or(&project.name)))
+      .collect();
+
+    let mut lines = vec![
+      Line::styled(format!("T{} {}", task.id, task.name), bold),
+      Line::raw(""),
+      Line::from(vec![
+        Span::raw("Status: "),
+        Span::styled(task.status.as_str(), status_style(&task.status)),
+        Span::raw(format!(
+          "  Priority: {}  Points: {}",
+          task.priority,
+          task
+            .point
+            .map(|point| point.to_string())
+            .unwrap_or_else(|| String::from("-"))
+        )),
+      ]),
+      Line::raw(format!(
+        "Author: {}  Owner: {}",
+        task
+          .author
+          .as_ref()
+          .map(User::mention)
+          .unwrap_or_else(|| task.author_phid.clone()),
+        task
+          .assigned
+          .as_ref()
+          .map(User::mention)
+          .unwrap_or_else(|| String::from("-"))
+      )),
+      Line::raw(format!("Boards: {}", task.board_labels().join(", "))),
+      Line::raw(format!("Tags: {}", tags.join(" "))),
+    ];
+
+    for revision in task_family.revisions.iter() {
+      lines.push(Line::raw(format!(
+        "D{} ({}) {}",
+        revision.id, revision.status_name, revision.title
+      )));
+    }
+
+    for commit in task_family.commits.iter() {
+      let short_identifier: String = commit.identifier.chars().take(12).collect();
+
+      lines.push(Line::raw(format!(
+        "{} {}",
+        short_identifier,
+        commit.summary()
+      )));
+    }
+
+    lines.push(Line::raw(""));
+    lines.extend(task.description.lines().map(Line::raw));
+    lines.push(Line::raw(""));
+    lines.push(Line::styled("Comments", bold));
+
+    match self.comments.get(&task.id) {
+      None => lines.push(Line::raw("Loading comments...")),
+      Some(Err(err)) => lines.push(Line::raw(format!("Could not load comments, {}", err))),
+      Some(Ok(comments)) if comments.is_empty() => lines.push(Line::raw("No comments")),
+      Some(Ok(comments)) => {
+        for comment in comments.iter() {
+          lines.push(Line::raw(""));
+          lines.push(Line::styled(
+            comment_header(comment),
+            Style::new().fg(Color::Cyan),
+          ));
+          lines.extend(
+            comment
+              .content
+              .lines()
+              .map(|line| Line::raw(format!("  {}", line))),
+          );
+        }
+      }
+    }
+
+    return lines;
+  }
+
+  fn status_line(&self) -> Line<'_> {
+    return match self.mode {
+      Mode::Filter => Line::raw(format!("/{}", self.tree.filter())),
+      Mode::Prompt(prompt) => Line::raw(format!("{}{}", prompt.label(), self.input)),
+      Mode::Browse if !self.message.is_empty() => Line::raw(self.message.as_str()),
+      Mode::Browse => Line::styled(HELP, Style::new().fg(Color::DarkGray)),
+    };
+  }
+}
+
+fn status_style(status: &str) -> Style {
+  return match status {
+    "open" => Style::new().fg(Color::Yellow),
+    "resolved" => Style::new().fg(Color::Green),
+    _ => Style::new().fg(Color::DarkGray),
+  };
+}
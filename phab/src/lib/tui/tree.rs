@@ -0,0 +1,236 @@
+use std::collections::HashSet;
+
+use phab_lib::dto::Task;
+use phab_lib::dto::TaskFamily;
+use phab_lib::dto::User;
+
+/// Child indexes from the root task down to a task in the family.
+pub type TaskPath = Vec<usize>;
+
+/// A visible line of the tree.
+pub struct TreeRow {
+  pub path: TaskPath,
+  pub has_children: bool,
+  pub is_expanded: bool,
+}
+
+impl TreeRow {
+  pub fn depth(&self) -> usize {
+    return self.path.len();
+  }
+}
+
+/// Browsing state of a task family tree: which tasks are collapsed, the filter
+/// and the selected row. Invalid tasks are hidden like in `phab task detail`.
+///
+/// While filtering, every matching task is shown along with its ancestors,
+/// regardless of collapsed tasks.
+/// ```
+/// # use phab_lib::dto::Task;
+/// # use phab_lib::dto::TaskFamily;
+/// use lib::tui::tree::TaskTreeView;
+///
+/// let mut tree = TaskTreeView::new(TaskFamily::new(Task::fake(1, "Epic"), vec![
+///   TaskFamily::new(Task::fake(2, "Login"), vec![
+///     TaskFamily::new(Task::fake(3, "Login api"), vec![]),
+///   ]),
+///   TaskFamily::new(Task::fake(4, "Logout"), vec![]),
+/// ]));
+///
+/// let visible_ids = |tree: &TaskTreeView| -> Vec<String> {
+///   return tree.rows().iter().map(|row| tree.task_family(&row.path).parent_task.id.clone()).collect();
+/// };
+///
+/// assert_eq!(visible_ids(&tree), vec!["1", "2", "3", "4"]);
+///
+/// tree.select_next();
+/// tree.collapse();
+/// assert_eq!(visible_ids(&tree), vec!["1", "2", "4"]);
+///
+/// tree.set_filter("api");
+/// assert_eq!(visible_ids(&tree), vec!["1", "2", "3"]);
+/// assert_eq!(tree.selected_task().unwrap().id, "2");
+/// ```
+pub struct TaskTreeView {
+  root: TaskFamily,
+  collapsed: HashSet<TaskPath>,
+  filter: String,
+  rows: Vec<TreeRow>,
+  selected: usize,
+}
+
+impl TaskTreeView {
+  pub fn new(root: TaskFamily) -> TaskTreeView {
+    let mut tree = TaskTreeView {
+      root,
+      collapsed: HashSet::new(),
+      filter: String::new(),
+      rows: vec![],
+      selected: 0,
+    };
+
+    tree.refresh_rows();
+
+    return tree;
+  }
+
+  pub fn rows(&self) -> &[TreeRow] {
+    return &self.rows;
+  }
+
+  pub fn selected(&self) -> usize {
+    return self.selected;
+  }
+
+  pub fn selected_path(&self) -> Option<&TaskPath> {
+    return self.rows.get(self.selected).map(|row| &row.path);
+  }
+
+  pub fn selected_task_family(&self) -> Option<&TaskFamily> {
+    return self
+      .selected_path()
+      .map(|path| self.task_family(path.as_slice()));
+  }
+
+  pub fn selected_task(&self) -> Option<&Task> {
+    return self
+      .selected_task_family()
+      .map(|task_family| &task_family.parent_task);
+  }
+
+  /// Panics if the path does not exist, paths should come from `rows`.
+  pub fn task_family(&self, path: &[usize]) -> &TaskFamily {
+    return path
+      .iter()
+      .fold(&self.root, |task_family, i| &task_family.children[*i]);
+  }
+
+  pub fn filter(&self) -> &str {
+    return &self.filter;
+  }
+
+  pub fn set_filter(&mut self, filter: &str) {
+    self.filter = filter.to_owned();
+    self.refresh_rows();
+  }
+
+  pub fn select_next(&mut self) {
+    if self.selected + 1 < self.rows.len() {
+      self.selected += 1;
+    }
+  }
+
+  pub fn select_previous(&mut self) {
+    self.selected = self.selected.saturating_sub(1);
+  }
+
+  pub fn expand(&mut self) {
+    if let Some(path) = self.selected_path().cloned() {
+      self.collapsed.remove(&path);
+      self.refresh_rows();
+    }
+  }
+
+  /// Collapse the selected task, or select its parent if there's nothing to collapse.
+  pub fn collapse(&mut self) {
+    let row = match self.rows.get(self.selected) {
+      Some(row) => row,
+      None => return,
+    };
+
+    if row.has_children && row.is_expanded && self.filter.is_empty() {
+      self.collapsed.insert(row.path.clone());
+      self.refresh_rows();
+
+      return;
+    }
+
+    let parent_path = &row.path[..row.path.len().saturating_sub(1)];
+
+    if let Some(parent_row) = self.rows.iter().position(|row| row.path == parent_path) {
+      self.selected = parent_row;
+    }
+  }
+
+  pub fn toggle(&mut self) {
+    match self.rows.get(self.selected) {
+      Some(row) if row.is_expanded => self.collapse(),
+      Some(_) => self.expand(),
+      None => {}
+    }
+  }
+
+  /// Replace the task at the given path e.g. after it's edited, children are kept.
+  pub fn replace_task(&mut self, path: &[usize], task: Task) {
+    let task_family = path.iter().fold(&mut self.root, |task_family, i| {
+      &mut task_family.children[*i]
+    });
+
+    task_family.parent_task = task;
+    self.refresh_rows();
+  }
+
+  /// Rebuild the visible rows and keep the selected task selected if it's still visible.
+  fn refresh_rows(&mut self) {
+    let selected_path = self.selected_path().cloned();
+    let mut rows = vec![];
+
+    self.collect_rows(&self.root, vec![], &mut rows);
+    self.rows = rows;
+
+    self.selected = selected_path
+      .and_then(|selected_path| self.rows.iter().position(|row| row.path == selected_path))
+      .unwrap_or_else(|| self.selected.min(self.rows.len().saturating_sub(1)));
+  }
+
+  /// Returns whether the task or one of its descendants is visible.
+  fn collect_rows(
+    &self,
+    task_family: &TaskFamily,
+    path: TaskPath,
+    rows: &mut Vec<TreeRow>,
+  ) -> bool {
+    if task_family.parent_task.status == "invalid" {
+      return false;
+    }
+
+    let is_filtering = !self.filter.is_empty();
+    let is_expanded = is_filtering || !self.collapsed.contains(&path);
+    let mut child_rows = vec![];
+
+    if is_expanded {
+      for (i, child) in task_family.children.iter().enumerate() {
+        let mut child_path = path.clone();
+
+        child_path.push(i);
+        self.collect_rows(child, child_path, &mut child_rows);
+      }
+    }
+
+    if is_filtering && child_rows.is_empty() && !self.matches_filter(&task_family.parent_task) {
+      return false;
+    }
+
+    rows.push(TreeRow {
+      path,
+      has_children: !task_family.children.is_empty(),
+      is_expanded,
+    });
+    rows.extend(child_rows);
+
+    return true;
+  }
+
+  /// Case insensitive match on task id, title, status and owner username.
+  fn matches_filter(&self, task: &Task) -> bool {
+    let owner = task
+      .assigned
+      .as_ref()
+      .map(User::mention)
+      .unwrap_or_default();
+
+    return format!("T{} {} {} {}", task.id, task.name, task.status, owner)
+      .to_lowercase()
+      .contains(&self.filter.to_lowercase());
+  }
+}
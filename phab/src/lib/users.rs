@@ -0,0 +1,25 @@
+use anyhow::anyhow;
+use phab_lib::client::phabricator::PhabricatorClient;
+
+use crate::types::ResultAnyError;
+
+/// Resolve a username into the user phid, `me` resolves to the api token owner
+/// and the username may be prefixed with `@`.
+pub async fn resolve_user_phid(
+  phabricator: &PhabricatorClient,
+  username: &str,
+) -> ResultAnyError<String> {
+  if username == "me" {
+    return Ok(phabricator.get_current_user().await?.phid);
+  }
+
+  let username = username.trim_start_matches('@');
+
+  return phabricator
+    .get_users_by_usernames(vec![username])
+    .await?
+    .into_iter()
+    .next()
+    .map(|user| user.phid)
+    .ok_or_else(|| anyhow!("Could not find user {}", username));
+}